@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Default number of API requests a client may make before it stops.
+pub const DEFAULT_MAX_REQUESTS: usize = 1500;
+
+/// Tracks how many API requests a single [`LinearClient`](super::LinearClient)
+/// has made against an optional upper bound.
+#[derive(Debug)]
+pub struct RequestBudget {
+    used: AtomicUsize,
+    max: Option<usize>,
+}
+
+impl RequestBudget {
+    /// Creates a budget capped at `max` requests, or unlimited when `None`.
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            used: AtomicUsize::new(0),
+            max,
+        }
+    }
+
+    /// Reserves one request, returning `false` if the budget is already spent.
+    pub fn try_acquire(&self) -> bool {
+        self.used
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| match self.max {
+                Some(max) if used >= max => None,
+                _ => Some(used + 1),
+            })
+            .is_ok()
+    }
+
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        self.max
+    }
+
+    /// Requests left before the budget is spent, or `None` when unlimited.
+    pub fn remaining(&self) -> Option<usize> {
+        self.max.map(|max| max.saturating_sub(self.used()))
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining() == Some(0)
+    }
+
+    pub fn reset(&self) {
+        self.used.store(0, Ordering::SeqCst);
+    }
+}
+
+impl Default for RequestBudget {
+    fn default() -> Self {
+        Self::new(Some(DEFAULT_MAX_REQUESTS))
+    }
+}
@@ -0,0 +1,91 @@
+use reqwest::StatusCode;
+use std::fmt;
+
+use super::types::GraphQLError;
+
+/// Errors returned by [`LinearClient`](super::LinearClient).
+#[derive(Debug)]
+pub enum LinearError {
+    /// The API key is missing, invalid or lacks access.
+    Authentication(String),
+    /// Linear kept rejecting requests for exceeding its quota after all
+    /// retries. `reset_at` is the UTC epoch milliseconds when it resets.
+    RateLimited { reset_at: Option<u64> },
+    /// The requested team or issue does not exist.
+    NotFound(String),
+    /// The query is too complex for Linear to run.
+    ComplexityExceeded(String),
+    /// The request could not be sent or the response could not be read.
+    Network(reqwest::Error),
+    /// Linear answered with an unexpected HTTP status.
+    Http(StatusCode),
+    /// Any other error reported in the GraphQL `errors` array.
+    GraphQL(Vec<GraphQLError>),
+    /// The client's own request budget is spent.
+    BudgetExhausted { max_requests: usize },
+}
+
+impl LinearError {
+    /// Classifies the errors of a GraphQL response by the first error that
+    /// maps to a specific variant.
+    pub fn from_graphql(errors: Vec<GraphQLError>) -> Self {
+        let specific = errors.iter().find_map(|error| {
+            let code = error.code().unwrap_or_default().to_ascii_uppercase();
+            let message = error.message.to_lowercase();
+
+            if matches!(
+                code.as_str(),
+                "AUTHENTICATION_ERROR" | "UNAUTHENTICATED" | "FORBIDDEN"
+            ) {
+                Some(Self::Authentication(error.message.clone()))
+            } else if code == "RATELIMITED" {
+                Some(Self::RateLimited { reset_at: None })
+            } else if code.contains("COMPLEXITY") || message.contains("complexity") {
+                Some(Self::ComplexityExceeded(error.message.clone()))
+            } else if code.contains("NOT_FOUND") || message.contains("not found") {
+                Some(Self::NotFound(error.message.clone()))
+            } else {
+                None
+            }
+        });
+
+        specific.unwrap_or(Self::GraphQL(errors))
+    }
+}
+
+impl fmt::Display for LinearError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Authentication(message) => write!(f, "authentication failed: {}", message),
+            Self::RateLimited { .. } => write!(f, "rate limited by Linear"),
+            Self::NotFound(message) => write!(f, "not found: {}", message),
+            Self::ComplexityExceeded(message) => {
+                write!(f, "query complexity exceeded: {}", message)
+            }
+            Self::Network(e) => write!(f, "network error: {}", e),
+            Self::Http(status) => write!(f, "Linear API returned {}", status),
+            Self::GraphQL(errors) => {
+                let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+                write!(f, "GraphQL errors: {}", messages.join("; "))
+            }
+            Self::BudgetExhausted { max_requests } => {
+                write!(f, "request budget of {} requests exhausted", max_requests)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinearError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for LinearError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Network(e)
+    }
+}
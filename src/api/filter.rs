@@ -0,0 +1,390 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+
+/// Workflow state types every Linear team has.
+pub const STATE_TYPES: [&str; 6] = [
+    "triage",
+    "backlog",
+    "unstarted",
+    "started",
+    "completed",
+    "canceled",
+];
+
+/// Builds the `IssueFilter` passed to the issues queries, so matching that
+/// Linear can do happens server-side instead of after downloading issues.
+/// All clauses must hold.
+#[derive(Debug, Clone, Default)]
+pub struct IssueFilter {
+    clauses: Vec<Value>,
+    ignore_accents: bool,
+}
+
+impl IssueFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the text clauses added after this also ignore accents, so
+    /// `cafe` matches `café`.
+    pub fn ignoring_accents(mut self, ignore_accents: bool) -> Self {
+        self.ignore_accents = ignore_accents;
+        self
+    }
+
+    /// A string comparator matching values that contain `term`.
+    fn contains(&self, term: &str) -> Value {
+        if self.ignore_accents {
+            json!({ "containsIgnoreCaseAndAccent": term })
+        } else {
+            json!({ "containsIgnoreCase": term })
+        }
+    }
+
+    /// Only issues of the given team.
+    pub fn team(mut self, team_id: &str) -> Self {
+        self.clauses
+            .push(json!({ "team": { "id": { "eq": team_id } } }));
+        self
+    }
+
+    /// Only issues of any of the given teams. An empty list means all teams.
+    pub fn teams(mut self, team_ids: &[String]) -> Self {
+        match team_ids {
+            [] => {}
+            [team_id] => return self.team(team_id),
+            team_ids => self
+                .clauses
+                .push(json!({ "team": { "id": { "in": team_ids } } })),
+        }
+        self
+    }
+
+    /// Only issues containing `term` (case-insensitively) in their title, or
+    /// in their description or a comment when those are enabled.
+    pub fn containing(mut self, term: &str, descriptions: bool, comments: bool) -> Self {
+        let contains = self.contains(term);
+        let mut fields = vec![json!({ "title": contains })];
+        if descriptions {
+            fields.push(json!({ "description": contains }));
+        }
+        if comments {
+            fields.push(json!({ "comments": { "some": { "body": contains } } }));
+        }
+        self.clauses.push(json!({ "or": fields }));
+        self
+    }
+
+    pub fn title_containing(mut self, term: &str) -> Self {
+        let contains = self.contains(term);
+        self.clauses.push(json!({ "title": contains }));
+        self
+    }
+
+    pub fn description_containing(mut self, term: &str) -> Self {
+        let contains = self.contains(term);
+        self.clauses.push(json!({ "description": contains }));
+        self
+    }
+
+    /// Only issues with a comment whose body contains `term`.
+    pub fn comment_containing(mut self, term: &str) -> Self {
+        let contains = self.contains(term);
+        self.clauses
+            .push(json!({ "comments": { "some": { "body": contains } } }));
+        self
+    }
+
+    /// Only issues with a comment by a user whose name contains `name`.
+    pub fn commented_by(mut self, name: &str) -> Self {
+        let contains = self.contains(name);
+        self.clauses
+            .push(json!({ "comments": { "some": { "user": { "name": contains } } } }));
+        self
+    }
+
+    /// Only the issues with the given IDs.
+    pub fn ids(mut self, ids: &[String]) -> Self {
+        self.clauses.push(json!({ "id": { "in": ids } }));
+        self
+    }
+
+    /// Only direct sub-issues of any of the issues with the given IDs.
+    pub fn parents(mut self, parent_ids: &[String]) -> Self {
+        self.clauses
+            .push(json!({ "parent": { "id": { "in": parent_ids } } }));
+        self
+    }
+
+    /// Only issues of the team with the given key.
+    pub fn team_key(mut self, key: &str) -> Self {
+        self.clauses
+            .push(json!({ "team": { "key": { "eqIgnoreCase": key } } }));
+        self
+    }
+
+    /// Only issues in any of the given workflow states, each matched by
+    /// type (see [`STATE_TYPES`]) or otherwise by name. `open` and `closed`
+    /// stand for the types of unfinished and finished states.
+    pub fn states(mut self, states: &[String]) -> Self {
+        let clauses = states
+            .iter()
+            .map(|state| match state.to_lowercase().as_str() {
+                "open" => json!({
+                    "state": { "type": { "in": ["triage", "backlog", "unstarted", "started"] } }
+                }),
+                "closed" => json!({ "state": { "type": { "in": ["completed", "canceled"] } } }),
+                state_type if STATE_TYPES.contains(&state_type) => {
+                    json!({ "state": { "type": { "eq": state_type } } })
+                }
+                _ => json!({ "state": { "name": { "eqIgnoreCase": state } } }),
+            })
+            .collect();
+        self.push_any(clauses);
+        self
+    }
+
+    /// Only issues with any of the given labels.
+    pub fn labels(mut self, labels: &[String]) -> Self {
+        let clauses = labels
+            .iter()
+            .map(|label| json!({ "labels": { "some": { "name": { "eqIgnoreCase": label } } } }))
+            .collect();
+        self.push_any(clauses);
+        self
+    }
+
+    /// Only issues assigned to any of the given users, matched by name,
+    /// display name or email. `me` is the API key's user and `none` means
+    /// unassigned.
+    pub fn assignees(mut self, assignees: &[String]) -> Self {
+        let clauses = assignees
+            .iter()
+            .map(|assignee| match assignee.to_lowercase().as_str() {
+                "me" => json!({ "assignee": { "isMe": { "eq": true } } }),
+                "none" => json!({ "assignee": { "null": true } }),
+                _ => json!({ "assignee": { "or": [
+                    { "name": { "eqIgnoreCase": assignee } },
+                    { "displayName": { "eqIgnoreCase": assignee } },
+                    { "email": { "eqIgnoreCase": assignee } },
+                ] } }),
+            })
+            .collect();
+        self.push_any(clauses);
+        self
+    }
+
+    /// Only issues with any of the given priorities, from 0 (none) and
+    /// 1 (urgent) to 4 (low).
+    pub fn priorities(mut self, priorities: &[u8]) -> Self {
+        if !priorities.is_empty() {
+            self.clauses
+                .push(json!({ "priority": { "in": priorities } }));
+        }
+        self
+    }
+
+    /// Only issues in any of the given projects; `none` means no project.
+    pub fn projects(mut self, projects: &[String]) -> Self {
+        let clauses = projects
+            .iter()
+            .map(|project| match project.to_lowercase().as_str() {
+                "none" => json!({ "project": { "null": true } }),
+                _ => json!({ "project": { "name": { "eqIgnoreCase": project } } }),
+            })
+            .collect();
+        self.push_any(clauses);
+        self
+    }
+
+    /// Only issues in any of the given cycles, each a cycle number, a name,
+    /// `current`, `next`, `previous` or `none`.
+    pub fn cycles(mut self, cycles: &[String]) -> Self {
+        let clauses = cycles
+            .iter()
+            .map(|cycle| match cycle.to_lowercase().as_str() {
+                "current" => json!({ "cycle": { "isActive": { "eq": true } } }),
+                "next" => json!({ "cycle": { "isNext": { "eq": true } } }),
+                "previous" => json!({ "cycle": { "isPrevious": { "eq": true } } }),
+                "none" => json!({ "cycle": { "null": true } }),
+                _ => match cycle.parse::<u32>() {
+                    Ok(number) => json!({ "cycle": { "number": { "eq": number } } }),
+                    Err(_) => json!({ "cycle": { "name": { "eqIgnoreCase": cycle } } }),
+                },
+            })
+            .collect();
+        self.push_any(clauses);
+        self
+    }
+
+    /// Only issues created within the given bounds, both inclusive.
+    pub fn created_between(
+        mut self,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Self {
+        if let Some(comparator) = date_comparator(after, before) {
+            self.clauses.push(json!({ "createdAt": comparator }));
+        }
+        self
+    }
+
+    /// Only issues updated at or after `since`.
+    pub fn updated_since(mut self, since: Option<DateTime<Utc>>) -> Self {
+        if let Some(comparator) = date_comparator(since, None) {
+            self.clauses.push(json!({ "updatedAt": comparator }));
+        }
+        self
+    }
+
+    /// Adds a clause that holds when any of `clauses` does.
+    fn push_any(&mut self, mut clauses: Vec<Value>) {
+        match clauses.len() {
+            0 => {}
+            1 => self.clauses.push(clauses.remove(0)),
+            _ => self.clauses.push(json!({ "or": clauses })),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self.clauses.as_slice() {
+            [] => json!({}),
+            [clause] => clause.clone(),
+            clauses => json!({ "and": clauses }),
+        }
+    }
+}
+
+/// A `DateComparator` for the given inclusive bounds, if there are any.
+fn date_comparator(after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> Option<Value> {
+    let timestamp = |at: DateTime<Utc>| at.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let mut comparator = serde_json::Map::new();
+    if let Some(after) = after {
+        comparator.insert("gte".to_string(), json!(timestamp(after)));
+    }
+    if let Some(before) = before {
+        comparator.insert("lte".to_string(), json!(timestamp(before)));
+    }
+    (!comparator.is_empty()).then_some(Value::Object(comparator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    fn date(value: &str) -> Option<DateTime<Utc>> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn test_empty_filter() {
+        assert_eq!(IssueFilter::new().teams(&[]).to_json(), json!({}));
+    }
+
+    #[test]
+    fn test_clauses_are_anded() {
+        let filter = IssueFilter::new()
+            .teams(&strings(&["t1", "t2"]))
+            .priorities(&[1, 2]);
+        assert_eq!(
+            filter.to_json(),
+            json!({ "and": [
+                { "team": { "id": { "in": ["t1", "t2"] } } },
+                { "priority": { "in": [1, 2] } },
+            ] })
+        );
+    }
+
+    #[test]
+    fn test_states() {
+        let filter = IssueFilter::new().states(&strings(&["open", "Started", "In Review"]));
+        assert_eq!(
+            filter.to_json(),
+            json!({ "or": [
+                { "state": { "type": { "in": ["triage", "backlog", "unstarted", "started"] } } },
+                { "state": { "type": { "eq": "started" } } },
+                { "state": { "name": { "eqIgnoreCase": "In Review" } } },
+            ] })
+        );
+        assert_eq!(
+            IssueFilter::new().states(&strings(&["closed"])).to_json(),
+            json!({ "state": { "type": { "in": ["completed", "canceled"] } } })
+        );
+    }
+
+    #[test]
+    fn test_assignees() {
+        let filter = IssueFilter::new().assignees(&strings(&["me", "none", "Ada"]));
+        assert_eq!(
+            filter.to_json(),
+            json!({ "or": [
+                { "assignee": { "isMe": { "eq": true } } },
+                { "assignee": { "null": true } },
+                { "assignee": { "or": [
+                    { "name": { "eqIgnoreCase": "Ada" } },
+                    { "displayName": { "eqIgnoreCase": "Ada" } },
+                    { "email": { "eqIgnoreCase": "Ada" } },
+                ] } },
+            ] })
+        );
+    }
+
+    #[test]
+    fn test_priorities() {
+        assert_eq!(
+            IssueFilter::new().priorities(&[0, 4]).to_json(),
+            json!({ "priority": { "in": [0, 4] } })
+        );
+        assert_eq!(IssueFilter::new().priorities(&[]).to_json(), json!({}));
+    }
+
+    #[test]
+    fn test_dates() {
+        let filter = IssueFilter::new()
+            .created_between(
+                date("2024-01-01T00:00:00Z"),
+                date("2024-01-31T23:59:59.999Z"),
+            )
+            .updated_since(date("2024-03-01T12:00:00Z"));
+        assert_eq!(
+            filter.to_json(),
+            json!({ "and": [
+                { "createdAt": {
+                    "gte": "2024-01-01T00:00:00.000Z",
+                    "lte": "2024-01-31T23:59:59.999Z",
+                } },
+                { "updatedAt": { "gte": "2024-03-01T12:00:00.000Z" } },
+            ] })
+        );
+        assert_eq!(
+            IssueFilter::new()
+                .created_between(None, date("2024-01-31T00:00:00Z"))
+                .to_json(),
+            json!({ "createdAt": { "lte": "2024-01-31T00:00:00.000Z" } })
+        );
+        assert_eq!(
+            IssueFilter::new()
+                .created_between(None, None)
+                .updated_since(None)
+                .to_json(),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn test_ids_and_parents() {
+        let ids = strings(&["a", "b"]);
+        assert_eq!(
+            IssueFilter::new().ids(&ids).to_json(),
+            json!({ "id": { "in": ["a", "b"] } })
+        );
+        assert_eq!(
+            IssueFilter::new().parents(&ids).to_json(),
+            json!({ "parent": { "id": { "in": ["a", "b"] } } })
+        );
+    }
+}
@@ -1,24 +1,159 @@
+pub mod budget;
+pub mod error;
+pub mod filter;
 pub mod queries;
+pub mod rate_limit;
 pub mod types;
 
-use anyhow::Result;
-use reqwest::Client;
+use reqwest::{Client, StatusCode};
 use serde::Deserialize;
 use serde_json::json;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use budget::RequestBudget;
+pub use error::LinearError;
+pub use filter::IssueFilter;
+use rate_limit::{RateLimitStatus, RetryPolicy};
 use types::{
-    Comment, GraphQLRequest, GraphQLResponse, Issue, IssueData, IssuesData, Team, TeamsData,
+    BatchCommentsData, Comment, GraphQLError, GraphQLRequest, GraphQLResponse, Issue, IssueRef,
+    IssueRefData, IssuesData, SubIssue, SubIssuesData, Team, TeamsData,
 };
 
-const LINEAR_API_URL: &str = "https://api.linear.app/graphql";
-const MAX_REQUESTS: usize = 1500;
+pub type Result<T, E = LinearError> = std::result::Result<T, E>;
 
-static REQUEST_COUNT: AtomicUsize = AtomicUsize::new(0);
+pub const LINEAR_API_URL: &str = "https://api.linear.app/graphql";
+
+/// Linear rejects any single query whose complexity exceeds this.
+const MAX_QUERY_COMPLEXITY: u64 = 10_000;
+
+/// Estimated complexity of one aliased comments page in a batch query, used
+/// until Linear reports the actual cost of a batch.
+const ESTIMATED_COMMENT_PAGE_COMPLEXITY: u64 = 260;
 
 pub struct LinearClient {
     client: Client,
     api_key: String,
+    endpoint: String,
+    budget: RequestBudget,
+    retry_policy: RetryPolicy,
+    rate_limit: Mutex<Option<RateLimitStatus>>,
+    comment_page_complexity: AtomicU64,
+    include_archived: bool,
+    on_rate_limit_wait: Option<WaitHook>,
+}
+
+/// Called with how long the client is about to wait for Linear's quota to
+/// reset, e.g. to tell the user.
+#[derive(Clone)]
+struct WaitHook(Arc<dyn Fn(Duration) + Send + Sync>);
+
+impl fmt::Debug for WaitHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WaitHook")
+    }
+}
+
+/// Configures a [`LinearClient`] before it is built.
+#[derive(Debug, Default)]
+pub struct LinearClientBuilder {
+    api_key: Option<String>,
+    endpoint: Option<String>,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    budget: Option<RequestBudget>,
+    retry_policy: RetryPolicy,
+    include_archived: bool,
+    on_rate_limit_wait: Option<WaitHook>,
+}
+
+impl LinearClientBuilder {
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// GraphQL endpoint every query is sent to. Defaults to [`LINEAR_API_URL`].
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Timeout applied to each HTTP request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Caps the number of API requests the client may make. `None` means unlimited.
+    pub fn max_requests(mut self, max_requests: Option<usize>) -> Self {
+        self.budget = Some(RequestBudget::new(max_requests));
+        self
+    }
+
+    /// Number of times a rate-limited, 5xx or network-failed request is retried.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.retry_policy.max_retries = max_retries;
+        self
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Also fetch archived and trashed issues, and archived comments, which
+    /// Linear leaves out by default.
+    pub fn include_archived(mut self, include_archived: bool) -> Self {
+        self.include_archived = include_archived;
+        self
+    }
+
+    /// Called before the client sleeps until Linear's exhausted quota
+    /// resets, with the time it will wait.
+    pub fn on_rate_limit_wait(mut self, hook: impl Fn(Duration) + Send + Sync + 'static) -> Self {
+        self.on_rate_limit_wait = Some(WaitHook(Arc::new(hook)));
+        self
+    }
+
+    pub fn build(self) -> Result<LinearClient> {
+        let api_key = match self.api_key {
+            Some(key) if !key.is_empty() => key,
+            _ => {
+                return Err(LinearError::Authentication(
+                    "API key is required".to_string(),
+                ))
+            }
+        };
+
+        let user_agent = self
+            .user_agent
+            .unwrap_or_else(|| format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+
+        let mut client = Client::builder().user_agent(user_agent);
+        if let Some(timeout) = self.timeout {
+            client = client.timeout(timeout);
+        }
+
+        Ok(LinearClient {
+            client: client.build()?,
+            api_key,
+            endpoint: self.endpoint.unwrap_or_else(|| LINEAR_API_URL.to_string()),
+            budget: self.budget.unwrap_or_default(),
+            retry_policy: self.retry_policy,
+            rate_limit: Mutex::new(None),
+            comment_page_complexity: AtomicU64::new(ESTIMATED_COMMENT_PAGE_COMPLEXITY),
+            include_archived: self.include_archived,
+            on_rate_limit_wait: self.on_rate_limit_wait,
+        })
+    }
 }
 
 impl LinearClient {
@@ -26,50 +161,205 @@ impl LinearClient {
         Self {
             client: Client::new(),
             api_key,
+            endpoint: LINEAR_API_URL.to_string(),
+            budget: RequestBudget::default(),
+            retry_policy: RetryPolicy::default(),
+            rate_limit: Mutex::new(None),
+            comment_page_complexity: AtomicU64::new(ESTIMATED_COMMENT_PAGE_COMPLEXITY),
+            include_archived: false,
+            on_rate_limit_wait: None,
         }
     }
 
+    pub fn builder() -> LinearClientBuilder {
+        LinearClientBuilder::default()
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    pub fn budget(&self) -> &RequestBudget {
+        &self.budget
+    }
+
     pub fn request_count(&self) -> usize {
-        REQUEST_COUNT.load(Ordering::SeqCst)
+        self.budget.used()
     }
 
-    pub fn max_requests(&self) -> usize {
-        MAX_REQUESTS
+    /// Request cap for this client, or `None` when unlimited.
+    pub fn max_requests(&self) -> Option<usize> {
+        self.budget.max()
     }
 
-    async fn execute_query<T: for<'de> Deserialize<'de>>(
-        &self,
-        query: &str,
-        variables: serde_json::Value,
-    ) -> Result<Option<T>> {
-        let count = REQUEST_COUNT.fetch_add(1, Ordering::SeqCst) + 1;
+    pub fn remaining_requests(&self) -> Option<usize> {
+        self.budget.remaining()
+    }
+
+    pub fn budget_exhausted(&self) -> bool {
+        self.budget.is_exhausted()
+    }
 
-        if count > MAX_REQUESTS {
-            anyhow::bail!("Rate limit reached: {} requests", MAX_REQUESTS);
+    pub fn reset_request_count(&self) {
+        self.budget.reset();
+    }
+
+    /// Linear's quota as reported by the most recent response, if any.
+    pub fn rate_limit(&self) -> Option<RateLimitStatus> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// Sleeps until Linear's quota resets if the last response reported it
+    /// as exhausted.
+    async fn wait_for_rate_limit_reset(&self) {
+        let wait = self.rate_limit().and_then(|rl| rl.wait_until_reset());
+        if let Some(wait) = wait {
+            if let Some(WaitHook(hook)) = &self.on_rate_limit_wait {
+                hook(wait);
+            }
+            tokio::time::sleep(wait).await;
         }
+    }
+
+    /// Delay before retrying a rate-limited request: until the quota resets
+    /// when Linear says so, otherwise the regular backoff.
+    fn rate_limited_delay(&self, attempt: u32) -> Duration {
+        self.rate_limit()
+            .and_then(|rl| rl.wait_until_reset())
+            .unwrap_or_else(|| self.retry_policy.backoff(attempt))
+    }
 
+    /// Sends a query, retrying rate-limited, 5xx and network failures, and
+    /// returns the raw GraphQL response including any `errors`.
+    async fn send_query<T: for<'de> Deserialize<'de>>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<GraphQLResponse<T>> {
         let request = GraphQLRequest {
             query: query.to_string(),
             variables,
         };
 
-        let response = self
-            .client
-            .post(LINEAR_API_URL)
-            .header("Content-Type", "application/json")
-            .header("Authorization", &self.api_key)
-            .json(&request)
-            .send()
-            .await?;
+        let mut attempt = 0;
+        loop {
+            self.wait_for_rate_limit_reset().await;
+
+            if !self.budget.try_acquire() {
+                return Err(LinearError::BudgetExhausted {
+                    max_requests: self.budget.max().unwrap_or_default(),
+                });
+            }
+
+            let can_retry = attempt < self.retry_policy.max_retries;
+
+            let response = match self
+                .client
+                .post(&self.endpoint)
+                .header("Content-Type", "application/json")
+                .header("Authorization", &self.api_key)
+                .json(&request)
+                .send()
+                .await
+            {
+                Ok(response) => response,
+                Err(e) if can_retry && (e.is_timeout() || e.is_connect()) => {
+                    tokio::time::sleep(self.retry_policy.backoff(attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
 
-        let gql_response: GraphQLResponse<T> = response.json().await?;
+            if let Some(status) = RateLimitStatus::from_headers(response.headers()) {
+                *self.rate_limit.lock().unwrap() = Some(status);
+            }
 
-        if let Some(errors) = gql_response.errors {
-            eprintln!("⚠ GraphQL errors: {:?}", errors);
-            return Ok(None);
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+                return Err(LinearError::Authentication(format!(
+                    "Linear API returned {}",
+                    status
+                )));
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+                if !can_retry {
+                    return Err(if status == StatusCode::TOO_MANY_REQUESTS {
+                        self.rate_limited_error()
+                    } else {
+                        LinearError::Http(status)
+                    });
+                }
+                let delay = if status == StatusCode::TOO_MANY_REQUESTS {
+                    self.rate_limited_delay(attempt)
+                } else {
+                    self.retry_policy.backoff(attempt)
+                };
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            let gql_response: GraphQLResponse<T> = match response.json().await {
+                Ok(gql_response) => gql_response,
+                Err(_) if !status.is_success() => return Err(LinearError::Http(status)),
+                Err(e) => return Err(e.into()),
+            };
+
+            // Linear reports an exhausted quota as a GraphQL error with a 400
+            let rate_limited = gql_response
+                .errors
+                .as_ref()
+                .is_some_and(|errors| errors.iter().any(|e| e.code() == Some("RATELIMITED")));
+            if rate_limited && can_retry {
+                tokio::time::sleep(self.rate_limited_delay(attempt)).await;
+                attempt += 1;
+                continue;
+            }
+
+            return Ok(gql_response);
         }
+    }
+
+    async fn execute_query<T: for<'de> Deserialize<'de>>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T> {
+        let response = self.send_query(query, variables).await?;
 
-        Ok(gql_response.data)
+        match (response.data, response.errors) {
+            (_, Some(errors)) if !errors.is_empty() => Err(self.graphql_error(errors)),
+            (Some(data), _) => Ok(data),
+            (None, _) => Err(LinearError::GraphQL(vec![GraphQLError {
+                message: "response contained no data".to_string(),
+                path: None,
+                extensions: None,
+            }])),
+        }
+    }
+
+    fn graphql_error(&self, errors: Vec<GraphQLError>) -> LinearError {
+        match LinearError::from_graphql(errors) {
+            LinearError::RateLimited { .. } => self.rate_limited_error(),
+            e => e,
+        }
+    }
+
+    fn rate_limited_error(&self) -> LinearError {
+        let reset_at = self
+            .rate_limit()
+            .and_then(|rl| rl.requests_reset.max(rl.complexity_reset));
+        LinearError::RateLimited { reset_at }
+    }
+
+    /// Linear's quota, sending a minimal query first if no response has
+    /// reported it yet.
+    pub async fn fetch_rate_limit(&self) -> Result<Option<RateLimitStatus>> {
+        if self.rate_limit().is_none() {
+            let _: serde_json::Value = self.execute_query(queries::VIEWER_QUERY, json!({})).await?;
+        }
+        Ok(self.rate_limit())
     }
 
     pub async fn fetch_teams(&self) -> Result<Vec<Team>> {
@@ -78,79 +368,212 @@ impl LinearClient {
 
         loop {
             let variables = json!({ "after": after });
-            let data: Option<TeamsData> =
-                self.execute_query(queries::TEAMS_QUERY, variables).await?;
+            let data: TeamsData = self.execute_query(queries::TEAMS_QUERY, variables).await?;
 
-            if let Some(data) = data {
-                teams.extend(data.teams.nodes);
-                if !data.teams.page_info.has_next_page {
-                    break;
-                }
-                after = data.teams.page_info.end_cursor;
-            } else {
+            teams.extend(data.teams.nodes);
+            if !data.teams.page_info.has_next_page {
                 break;
             }
+            after = data.teams.page_info.end_cursor;
         }
 
         Ok(teams)
     }
 
-    pub async fn fetch_issues(&self, team_id: &str) -> Result<Vec<Issue>> {
+    /// Fetches every issue of the given teams, or of all teams when
+    /// `team_ids` is empty.
+    pub async fn fetch_issues(&self, team_ids: &[String]) -> Result<Vec<Issue>> {
+        self.fetch_filtered_issues(&IssueFilter::new().teams(team_ids), None)
+            .await
+    }
+
+    /// Fetches every issue matching `filter`, optionally with the first
+    /// `comments_per_issue` comments of each inline.
+    pub async fn fetch_filtered_issues(
+        &self,
+        filter: &IssueFilter,
+        comments_per_issue: Option<usize>,
+    ) -> Result<Vec<Issue>> {
+        match comments_per_issue {
+            Some(first) => {
+                let variables = json!({
+                    "filter": filter.to_json(),
+                    "commentsFirst": first,
+                    "includeArchived": self.include_archived,
+                });
+                self.fetch_issue_pages(queries::ISSUES_WITH_COMMENTS_QUERY, variables)
+                    .await
+            }
+            None => {
+                let variables = json!({
+                    "filter": filter.to_json(),
+                    "includeArchived": self.include_archived,
+                });
+                self.fetch_issue_pages(queries::ISSUES_QUERY, variables)
+                    .await
+            }
+        }
+    }
+
+    async fn fetch_issue_pages(
+        &self,
+        query: &str,
+        mut variables: serde_json::Value,
+    ) -> Result<Vec<Issue>> {
         let mut issues = Vec::new();
         let mut after: Option<String> = None;
 
         loop {
-            if REQUEST_COUNT.load(Ordering::SeqCst) >= MAX_REQUESTS {
+            if self.budget_exhausted() {
                 break;
             }
 
-            let variables = json!({ "teamId": team_id, "after": after });
-            let data: Option<IssuesData> =
-                self.execute_query(queries::ISSUES_QUERY, variables).await?;
+            variables["after"] = json!(after);
+            let data: IssuesData = self.execute_query(query, variables.clone()).await?;
 
-            if let Some(data) = data {
-                issues.extend(data.issues.nodes);
-                if !data.issues.page_info.has_next_page {
-                    break;
-                }
-                after = data.issues.page_info.end_cursor;
-            } else {
+            issues.extend(data.issues.nodes);
+            if !data.issues.page_info.has_next_page {
                 break;
             }
+            after = data.issues.page_info.end_cursor;
         }
 
         Ok(issues)
     }
 
-    pub async fn fetch_comments(&self, issue_id: &str) -> Result<Vec<Comment>> {
-        let mut comments = Vec::new();
-        let mut after: Option<String> = None;
+    /// Fetches an issue, by ID or identifier such as `ENG-123`, followed by
+    /// all its sub-issues, their sub-issues and so on.
+    pub async fn fetch_subtree(&self, issue_id: &str) -> Result<Vec<IssueRef>> {
+        let data: IssueRefData = self
+            .execute_query(queries::ISSUE_QUERY, json!({ "id": issue_id }))
+            .await?;
+        let Some(root) = data.issue else {
+            return Err(LinearError::NotFound(format!("issue {}", issue_id)));
+        };
+
+        // Walk down one level of sub-issues per round of queries
+        let mut parents = vec![root.id.clone()];
+        let mut subtree = vec![root];
+        while !parents.is_empty() {
+            let children = self.fetch_sub_issues(&parents).await?;
+            parents = children
+                .iter()
+                .map(|child| child.issue.id.clone())
+                .collect();
+            subtree.extend(children.into_iter().map(|child| child.issue));
+        }
 
+        Ok(subtree)
+    }
+
+    /// Fetches the direct sub-issues of the issues with the given IDs.
+    pub async fn fetch_sub_issues(&self, parent_ids: &[String]) -> Result<Vec<SubIssue>> {
+        let filter = IssueFilter::new().parents(parent_ids);
+        let mut variables = json!({
+            "filter": filter.to_json(),
+            "includeArchived": self.include_archived,
+        });
+        let mut sub_issues = Vec::new();
         loop {
-            if REQUEST_COUNT.load(Ordering::SeqCst) >= MAX_REQUESTS {
+            let data: SubIssuesData = self
+                .execute_query(queries::SUB_ISSUES_QUERY, variables.clone())
+                .await?;
+            sub_issues.extend(data.issues.nodes);
+            if !data.issues.page_info.has_next_page {
                 break;
             }
+            variables["after"] = json!(data.issues.page_info.end_cursor);
+        }
 
-            let variables = json!({ "issueId": issue_id, "after": after });
-            let data: Option<IssueData> = self
-                .execute_query(queries::ISSUE_COMMENTS_QUERY, variables)
-                .await?;
+        Ok(sub_issues)
+    }
+
+    /// Number of issues whose comments fit in one batch query without
+    /// exceeding Linear's complexity limit, keeping some headroom.
+    pub fn comments_batch_size(&self) -> usize {
+        let per_page = self.comment_page_complexity.load(Ordering::Relaxed).max(1);
+        ((MAX_QUERY_COMPLEXITY * 8 / 10) / per_page).max(1) as usize
+    }
 
-            if let Some(data) = data {
-                if let Some(issue) = data.issue {
-                    comments.extend(issue.comments.nodes);
-                    if !issue.comments.page_info.has_next_page {
-                        break;
+    /// Fetches all comments of many issues using aliased batch queries.
+    /// Each entry is an issue ID and the cursor to start after; results are
+    /// returned in the same order. Fails with
+    /// [`LinearError::BudgetExhausted`] only if the budget was spent before
+    /// the first batch; later on the comments fetched so far are returned.
+    pub async fn fetch_comments_batch(
+        &self,
+        issues: &[(String, Option<String>)],
+    ) -> Result<Vec<Vec<Comment>>> {
+        let mut comments: Vec<Vec<Comment>> = issues.iter().map(|_| Vec::new()).collect();
+        let mut pending: Vec<(usize, Option<String>)> = issues
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, after))| (idx, after.clone()))
+            .collect();
+
+        let mut first_batch = true;
+        while !pending.is_empty() {
+            let batch_len = self.comments_batch_size().min(pending.len());
+            let batch: Vec<(usize, Option<String>)> = pending.drain(..batch_len).collect();
+
+            let mut variables = serde_json::Map::new();
+            variables.insert("includeArchived".to_string(), json!(self.include_archived));
+            for (alias, (idx, after)) in batch.iter().enumerate() {
+                variables.insert(format!("i{alias}"), json!(issues[*idx].0));
+                variables.insert(format!("a{alias}"), json!(after));
+            }
+
+            let query = queries::batch_comments_query(batch.len());
+            let response: GraphQLResponse<BatchCommentsData> =
+                match self.send_query(&query, variables.into()).await {
+                    Ok(response) => response,
+                    Err(LinearError::BudgetExhausted { .. }) if !first_batch => break,
+                    Err(e) => return Err(e),
+                };
+            self.record_batch_complexity(batch.len());
+            first_batch = false;
+
+            // An issue deleted since it was listed only fails its own alias
+            let errors: Vec<GraphQLError> = response
+                .errors
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|e| !(e.path.is_some() && is_not_found(e)))
+                .collect();
+            if !errors.is_empty() {
+                return Err(self.graphql_error(errors));
+            }
+            let Some(mut data) = response.data else {
+                break;
+            };
+
+            for (alias, (idx, _)) in batch.into_iter().enumerate() {
+                if let Some(Some(issue)) = data.remove(&format!("i{alias}")) {
+                    comments[idx].extend(issue.comments.nodes);
+                    if issue.comments.page_info.has_next_page {
+                        pending.push((idx, issue.comments.page_info.end_cursor));
                     }
-                    after = issue.comments.page_info.end_cursor;
-                } else {
-                    break;
                 }
-            } else {
-                break;
             }
         }
 
         Ok(comments)
     }
+
+    /// Updates the per-page complexity estimate from the cost Linear
+    /// reported for a batch of `batch_len` comment pages.
+    fn record_batch_complexity(&self, batch_len: usize) {
+        if let Some(complexity) = self.rate_limit().and_then(|rl| rl.complexity) {
+            let per_page = complexity.div_ceil(batch_len as u64).max(1);
+            self.comment_page_complexity
+                .store(per_page, Ordering::Relaxed);
+        }
+    }
+}
+
+fn is_not_found(error: &GraphQLError) -> bool {
+    matches!(
+        LinearError::from_graphql(vec![error.clone()]),
+        LinearError::NotFound(_)
+    )
 }
@@ -1,3 +1,31 @@
+/// Selection set shared by every comments connection we query.
+macro_rules! comment_connection_fields {
+    () => {
+        r#"
+                nodes { id body user { name } createdAt url }
+                pageInfo { hasNextPage endCursor }"#
+    };
+}
+
+/// Fields selected for every issue we search.
+macro_rules! issue_fields {
+    () => {
+        r#"
+                id identifier title url description createdAt updatedAt team { key name }
+                state { name type } assignee { name } creator { name }
+                priority priorityLabel estimate labels(first: 20) { nodes { name } }
+                project { name } cycle { number name } completedAt canceledAt archivedAt trashed
+                parent { id identifier title url }"#
+    };
+}
+
+/// The cheapest query there is, sent only to read Linear's quota headers.
+pub const VIEWER_QUERY: &str = r#"
+    query Viewer {
+        viewer { id }
+    }
+"#;
+
 pub const TEAMS_QUERY: &str = r#"
     query Teams($after: String) {
         teams(first: 50, after: $after) {
@@ -7,23 +35,82 @@ pub const TEAMS_QUERY: &str = r#"
     }
 "#;
 
-pub const ISSUES_QUERY: &str = r#"
-    query Issues($teamId: ID!, $after: String) {
-        issues(first: 100, after: $after, orderBy: updatedAt, filter: { team: { id: { eq: $teamId } } }) {
-            nodes { id identifier title url description team { key name } }
+pub const ISSUES_QUERY: &str = concat!(
+    r#"
+    query Issues($filter: IssueFilter, $after: String, $includeArchived: Boolean) {
+        issues(
+            first: 100, after: $after, orderBy: updatedAt, filter: $filter,
+            includeArchived: $includeArchived
+        ) {
+            nodes {"#,
+    issue_fields!(),
+    r#"
+            }
             pageInfo { hasNextPage endCursor }
         }
     }
-"#;
+"#
+);
 
-pub const ISSUE_COMMENTS_QUERY: &str = r#"
-    query IssueComments($issueId: String!, $after: String) {
-        issue(id: $issueId) {
-            id
-            comments(first: 100, after: $after) {
-                nodes { id body user { name } createdAt url }
-                pageInfo { hasNextPage endCursor }
+pub const ISSUES_WITH_COMMENTS_QUERY: &str = concat!(
+    r#"
+    query IssuesWithComments(
+        $filter: IssueFilter, $after: String, $commentsFirst: Int!, $includeArchived: Boolean
+    ) {
+        issues(
+            first: 100, after: $after, orderBy: updatedAt, filter: $filter,
+            includeArchived: $includeArchived
+        ) {
+            nodes {"#,
+    issue_fields!(),
+    r#"
+                comments(first: $commentsFirst, includeArchived: $includeArchived) {"#,
+    comment_connection_fields!(),
+    r#"
+                }
             }
+            pageInfo { hasNextPage endCursor }
+        }
+    }
+"#
+);
+
+pub const ISSUE_QUERY: &str = r#"
+    query Issue($id: String!) {
+        issue(id: $id) { id identifier title url }
+    }
+"#;
+
+/// Lists issues matching a filter by reference only, with the ID of their
+/// parent, e.g. the sub-issues of some issues.
+pub const SUB_ISSUES_QUERY: &str = r#"
+    query SubIssues($filter: IssueFilter, $after: String, $includeArchived: Boolean) {
+        issues(first: 100, after: $after, filter: $filter, includeArchived: $includeArchived) {
+            nodes { id identifier title url parent { id } }
+            pageInfo { hasNextPage endCursor }
         }
     }
 "#;
+
+/// Selection set of a comments page, as used by [`batch_comments_query`].
+pub const COMMENT_CONNECTION_FIELDS: &str = comment_connection_fields!();
+
+/// Builds a query fetching one comments page for each of `count` issues in
+/// a single request, using the aliases `i0`, `i1`, ... with the issue ID in
+/// `$i<n>` and the page cursor in `$a<n>`, plus `$includeArchived` for all.
+pub fn batch_comments_query(count: usize) -> String {
+    let params: Vec<String> = (0..count)
+        .map(|i| format!("$i{i}: String!, $a{i}: String"))
+        .chain(std::iter::once("$includeArchived: Boolean".to_string()))
+        .collect();
+
+    let mut query = format!("\n    query BatchComments({}) {{\n", params.join(", "));
+    for i in 0..count {
+        query.push_str(&format!(
+            "        i{i}: issue(id: $i{i}) {{\n            id\n            comments(first: 100, after: $a{i}, includeArchived: $includeArchived) {{{}\n            }}\n        }}\n",
+            COMMENT_CONNECTION_FIELDS
+        ));
+    }
+    query.push_str("    }\n");
+    query
+}
@@ -0,0 +1,93 @@
+use reqwest::header::HeaderMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Rate-limit quota reported by Linear in the response headers of the most
+/// recent request. Reset times are UTC epoch milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub requests_limit: Option<u64>,
+    pub requests_remaining: Option<u64>,
+    pub requests_reset: Option<u64>,
+    pub complexity_limit: Option<u64>,
+    pub complexity_remaining: Option<u64>,
+    pub complexity_reset: Option<u64>,
+    /// Complexity Linear charged for the request itself.
+    pub complexity: Option<u64>,
+}
+
+impl RateLimitStatus {
+    /// Reads the `X-RateLimit-*` and `X-Complexity` headers, returning `None`
+    /// when the response carried none of them.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let read = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+
+        let status = Self {
+            requests_limit: read("x-ratelimit-requests-limit"),
+            requests_remaining: read("x-ratelimit-requests-remaining"),
+            requests_reset: read("x-ratelimit-requests-reset"),
+            complexity_limit: read("x-ratelimit-complexity-limit"),
+            complexity_remaining: read("x-ratelimit-complexity-remaining"),
+            complexity_reset: read("x-ratelimit-complexity-reset"),
+            complexity: read("x-complexity"),
+        };
+
+        (status != Self::default()).then_some(status)
+    }
+
+    /// How long to wait until an exhausted quota resets, if it is exhausted
+    /// and the reset time is still in the future.
+    pub fn wait_until_reset(&self) -> Option<Duration> {
+        let mut reset = None;
+        if self.requests_remaining == Some(0) {
+            reset = reset.max(self.requests_reset);
+        }
+        if self.complexity_remaining == Some(0) {
+            reset = reset.max(self.complexity_reset);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        reset
+            .filter(|&reset| reset > now)
+            .map(|reset| Duration::from_millis(reset - now))
+    }
+}
+
+/// Controls how failed requests are retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff for the given zero-based attempt, with "equal
+    /// jitter": half the delay is fixed and the other half is random.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        let half = exp / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
@@ -1,4 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::text::Snippet;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GraphQLRequest {
@@ -9,7 +14,26 @@ pub struct GraphQLRequest {
 #[derive(Debug, Deserialize)]
 pub struct GraphQLResponse<T> {
     pub data: Option<T>,
-    pub errors: Option<Vec<serde_json::Value>>,
+    pub errors: Option<Vec<GraphQLError>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQLError {
+    pub message: String,
+    #[serde(default)]
+    pub path: Option<Vec<serde_json::Value>>,
+    #[serde(default)]
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl GraphQLError {
+    /// The `extensions.code` Linear attaches to the error, if any.
+    pub fn code(&self) -> Option<&str> {
+        self.extensions
+            .as_ref()
+            .and_then(|ext| ext.get("code"))
+            .and_then(|code| code.as_str())
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -23,10 +47,18 @@ pub struct IssuesData {
 }
 
 #[derive(Debug, Deserialize)]
-pub struct IssueData {
-    pub issue: Option<IssueWithComments>,
+pub struct SubIssuesData {
+    pub issues: Connection<SubIssue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueRefData {
+    pub issue: Option<IssueRef>,
 }
 
+/// Response of a batch comments query, keyed by alias (`i0`, `i1`, ...).
+pub type BatchCommentsData = HashMap<String, Option<IssueWithComments>>;
+
 #[derive(Debug, Deserialize)]
 pub struct Connection<T> {
     pub nodes: Vec<T>,
@@ -42,7 +74,7 @@ pub struct PageInfo {
     pub end_cursor: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Team {
     pub id: String,
     pub key: String,
@@ -56,7 +88,114 @@ pub struct Issue {
     pub title: String,
     pub url: String,
     pub description: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: DateTime<Utc>,
     pub team: Option<TeamInfo>,
+    #[serde(flatten)]
+    pub details: IssueDetails,
+    /// The issue this is a sub-issue of.
+    pub parent: Option<IssueRef>,
+    /// First page of comments, only present when fetched inline.
+    #[serde(default)]
+    pub comments: Option<Connection<Comment>>,
+}
+
+/// Workflow, ownership and planning details of an issue.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct IssueDetails {
+    pub state: Option<WorkflowState>,
+    pub assignee: Option<User>,
+    pub creator: Option<User>,
+    /// 0 for no priority, then 1 (urgent) to 4 (low).
+    #[serde(default)]
+    pub priority: f64,
+    #[serde(rename = "priorityLabel", default)]
+    pub priority_label: String,
+    pub estimate: Option<f64>,
+    #[serde(default)]
+    pub labels: Nodes<Label>,
+    pub project: Option<Project>,
+    pub cycle: Option<Cycle>,
+    #[serde(rename = "completedAt")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(rename = "canceledAt")]
+    pub canceled_at: Option<DateTime<Utc>>,
+    /// Only set when archived issues are included in the search.
+    #[serde(rename = "archivedAt")]
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Whether the issue is in the trash, which also archives it.
+    #[serde(default)]
+    pub trashed: Option<bool>,
+}
+
+impl IssueDetails {
+    pub fn is_archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    pub fn is_trashed(&self) -> bool {
+        self.trashed.unwrap_or(false)
+    }
+}
+
+/// The nodes of a connection fetched without paging.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Nodes<T> {
+    pub nodes: Vec<T>,
+}
+
+impl<T> Default for Nodes<T> {
+    fn default() -> Self {
+        Self { nodes: Vec::new() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WorkflowState {
+    pub name: String,
+    /// `triage`, `backlog`, `unstarted`, `started`, `completed` or `canceled`.
+    #[serde(rename = "type")]
+    pub state_type: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Label {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Project {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cycle {
+    pub number: f64,
+    pub name: Option<String>,
+}
+
+/// Another issue an issue refers to, such as its parent or a sub-issue.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IssueRef {
+    pub id: String,
+    pub identifier: String,
+    pub title: String,
+    pub url: String,
+}
+
+/// An issue listed with the ID of its parent.
+#[derive(Debug, Deserialize)]
+pub struct SubIssue {
+    #[serde(flatten)]
+    pub issue: IssueRef,
+    pub parent: Option<IssueId>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IssueId {
+    pub id: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -77,11 +216,11 @@ pub struct Comment {
     pub body: String,
     pub user: Option<User>,
     #[serde(rename = "createdAt")]
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     pub url: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct User {
     pub name: String,
 }
@@ -92,14 +231,38 @@ pub struct Match {
     pub id: String,
     pub title: String,
     pub url: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub details: IssueDetails,
+    pub parent: Option<IssueRef>,
+    /// Identifiers of the sub-issues, only listed with `--rollup`.
+    pub children: Vec<String>,
+    /// Relevance to the query; higher is better.
+    pub score: f64,
     pub in_title: bool,
     pub in_desc: bool,
+    /// Byte ranges of the matches in `title`.
+    pub title_spans: Vec<Range<usize>>,
+    /// Byte ranges of the matches in the issue description.
+    pub desc_spans: Vec<Range<usize>>,
+    /// Excerpts of the description around its matches.
+    pub desc_snippets: Vec<Snippet>,
     pub comments_matched: Vec<CommentHit>,
+    /// The distinct words the terms matched, as written in the issue, e.g.
+    /// the misspellings a fuzzy search found.
+    pub matched_words: Vec<String>,
+    /// Which of several search terms the issue matched; empty when
+    /// searching for a single term.
+    pub matched_terms: Vec<String>,
 }
 
 #[derive(Debug)]
 pub struct CommentHit {
     pub commenter: String,
-    pub created_at: String,
+    pub created_at: DateTime<Utc>,
     pub url: String,
+    /// Byte ranges of the matches in the comment body.
+    pub spans: Vec<Range<usize>>,
+    /// Excerpts of the comment body around its matches.
+    pub snippets: Vec<Snippet>,
 }
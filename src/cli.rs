@@ -1,21 +1,107 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use clap::Parser;
+use std::path::PathBuf;
+
+use crate::api::budget::DEFAULT_MAX_REQUESTS;
+use crate::api::LINEAR_API_URL;
+use crate::search::{SortOrder, DEFAULT_CONCURRENCY, DEFAULT_CONTEXT};
 
 #[derive(Parser, Debug)]
 #[command(name = "linsearch")]
 #[command(about = "Search Linear issues for specific terms", long_about = None)]
 #[command(version)]
 pub struct Args {
-    /// Search term to look for
-    #[arg(value_name = "SEARCH_TERM")]
-    pub search_term: String,
+    /// Search terms to look for; issues matching any of them are reported
+    #[arg(value_name = "SEARCH_TERM", required_unless_present = "terms_file")]
+    pub search_terms: Vec<String>,
+
+    /// Read more search terms from a file, one per line (blank lines and
+    /// lines starting with # are skipped)
+    #[arg(long, value_name = "PATH")]
+    pub terms_file: Option<PathBuf>,
 
     /// Linear API key
     #[arg(short, long, env = "LINEAR_API_KEY")]
     pub api_key: Option<String>,
 
-    /// Team ID to search within
-    #[arg(short, long)]
-    pub team_id: Option<String>,
+    /// GraphQL endpoint to send queries to (e.g. a proxy or local mock server)
+    #[arg(long, env = "LINEAR_API_URL", default_value = LINEAR_API_URL)]
+    pub endpoint: String,
+
+    /// Request timeout in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 30)]
+    pub timeout: u64,
+
+    /// Maximum number of API requests per run (0 for unlimited)
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_REQUESTS)]
+    pub max_requests: usize,
+
+    /// Number of times to retry rate-limited, 5xx or failed requests
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub max_retries: u32,
+
+    /// Team key (e.g. ENG), name or ID to search within (repeat to search several teams)
+    #[arg(
+        short = 't',
+        long = "team",
+        visible_alias = "team-id",
+        value_name = "TEAM"
+    )]
+    pub teams: Vec<String>,
+
+    /// Search across all teams
+    #[arg(long, conflicts_with = "teams")]
+    pub all_teams: bool,
+
+    /// Only search issues in this workflow state, by name or type (e.g. "In Review",
+    /// started, open, closed); repeat for any of several
+    #[arg(long = "state", value_name = "STATE")]
+    pub states: Vec<String>,
+
+    /// Only search issues with this label; repeat for any of several
+    #[arg(long = "label", value_name = "LABEL")]
+    pub labels: Vec<String>,
+
+    /// Only search issues assigned to this user (name, display name, email, "me" or
+    /// "none"); repeat for any of several
+    #[arg(long = "assignee", value_name = "USER")]
+    pub assignees: Vec<String>,
+
+    /// Only search issues with this priority (urgent, high, medium, low, none or
+    /// 0-4); repeat for any of several
+    #[arg(long = "priority", value_name = "PRIORITY", value_parser = parse_priority)]
+    pub priorities: Vec<u8>,
+
+    /// Only search issues in this project ("none" for no project); repeat for any
+    /// of several
+    #[arg(long = "project", value_name = "PROJECT")]
+    pub projects: Vec<String>,
+
+    /// Only search issues in this cycle (number, name, current, next, previous or
+    /// "none"); repeat for any of several
+    #[arg(long = "cycle", value_name = "CYCLE")]
+    pub cycles: Vec<String>,
+
+    /// Only search issues created on or after DATE (e.g. 2024-01-31, or 30d, 12w, 6m, 1y ago);
+    /// also limits which comments are searched
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub created_after: Option<DateTime<Utc>>,
+
+    /// Only search issues created on or before DATE; also limits which comments are searched
+    #[arg(long, value_name = "DATE", value_parser = parse_end_date)]
+    pub created_before: Option<DateTime<Utc>>,
+
+    /// Only search issues updated on or after DATE; also limits which comments are searched
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub updated_since: Option<DateTime<Utc>>,
+
+    /// Only search this issue (e.g. ENG-123) and its sub-issues, recursively
+    #[arg(long, value_name = "ISSUE")]
+    pub within: Option<String>,
+
+    /// Also search archived and trashed issues, and archived comments
+    #[arg(long)]
+    pub include_archived: bool,
 
     /// Search in descriptions
     #[arg(short, long)]
@@ -25,6 +111,80 @@ pub struct Args {
     #[arg(short, long)]
     pub comments: bool,
 
+    /// Treat the search term as a regular expression
+    #[arg(long)]
+    pub regex: bool,
+
+    /// Search for each term exactly as written, without the query syntax
+    #[arg(long, conflicts_with = "regex")]
+    pub literal: bool,
+
+    /// Only match whole words, e.g. `log` but not `login`
+    #[arg(short, long)]
+    pub word: bool,
+
+    /// Match letter case exactly
+    #[arg(long)]
+    pub case_sensitive: bool,
+
+    /// Ignore accents, so `cafe` also matches `café`
+    #[arg(long)]
+    pub fold_accents: bool,
+
+    /// Tolerate up to N typos per word (default 1 when given without a value)
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "1",
+        conflicts_with_all = ["regex", "server_side"]
+    )]
+    pub fuzzy: Option<usize>,
+
+    /// Only search the prose of descriptions and comments, not code
+    #[arg(long, conflicts_with_all = ["in_code", "raw_markdown"])]
+    pub prose_only: bool,
+
+    /// Only search inline code and code blocks in descriptions and comments
+    #[arg(long, conflicts_with = "raw_markdown")]
+    pub in_code: bool,
+
+    /// Search the Markdown source as is, including link targets and mention IDs
+    #[arg(long)]
+    pub raw_markdown: bool,
+
+    /// Let Linear filter issues by the search term instead of downloading every issue
+    #[arg(long, conflicts_with = "regex")]
+    pub server_side: bool,
+
+    /// Report matching comments even for issues whose title or description
+    /// already matched (fetches their comments too; implies --comments)
+    #[arg(long)]
+    pub all_locations: bool,
+
+    /// List matching sub-issues under their parent issue instead of grouping
+    /// results by team; the parent is shown even if it did not match. Also
+    /// lists each match's sub-issues, which takes extra requests
+    #[arg(long)]
+    pub rollup: bool,
+
+    /// Characters of context to show on each side of a match
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_CONTEXT)]
+    pub context: usize,
+
+    /// Comments to fetch inline with each page of issues (0 to query comments per issue)
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub inline_comments: usize,
+
+    /// Number of comment requests to run in parallel
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_CONCURRENCY)]
+    pub concurrency: usize,
+
+    /// How to order the results
+    #[arg(long, value_enum, default_value_t = SortOrder::Relevance)]
+    pub sort: SortOrder,
+
     /// Output file path (defaults to linsearch-results.md)
     #[arg(short, long, default_value = "linsearch-results.md")]
     pub output: String,
@@ -33,3 +193,122 @@ pub struct Args {
     #[arg(long)]
     pub terminal: bool,
 }
+
+/// Parses a priority name or its number, 0 (none) to 4 (low).
+fn parse_priority(value: &str) -> Result<u8, String> {
+    match value.to_lowercase().as_str() {
+        "none" | "no" => Ok(0),
+        "urgent" => Ok(1),
+        "high" => Ok(2),
+        "medium" | "normal" => Ok(3),
+        "low" => Ok(4),
+        other => match other.parse::<u8>() {
+            Ok(priority) if priority <= 4 => Ok(priority),
+            _ => Err("expected urgent, high, medium, low, none or 0-4".to_string()),
+        },
+    }
+}
+
+/// Parses an ISO 8601 date or timestamp, or a time ago: a number of days,
+/// weeks, months or years such as `30d`, `12w`, `6m` or `1y`. A date
+/// stands for its start.
+fn parse_date(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_date_bound(value, false)
+}
+
+/// Like [`parse_date`], but a date stands for its end, so an upper bound
+/// includes the whole day.
+fn parse_end_date(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_date_bound(value, true)
+}
+
+fn parse_date_bound(value: &str, end_of_day: bool) -> Result<DateTime<Utc>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let time = if end_of_day {
+            date.and_hms_milli_opt(23, 59, 59, 999)
+        } else {
+            date.and_hms_opt(0, 0, 0)
+        };
+        return Ok(time.unwrap_or_default().and_utc());
+    }
+
+    let invalid = || "expected a date like 2024-01-31 or a time ago like 30d, 12w, 6m or 1y";
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (count, unit) = value.split_at(split);
+    let count: i64 = count.parse().map_err(|_| invalid().to_string())?;
+    let days_per_unit = match unit {
+        "d" => 1,
+        "w" => 7,
+        "m" => 30,
+        "y" => 365,
+        _ => return Err(invalid().to_string()),
+    };
+    count
+        .checked_mul(days_per_unit)
+        .and_then(Duration::try_days)
+        .and_then(|ago| Utc::now().checked_sub_signed(ago))
+        .ok_or_else(|| invalid().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts `value` parses to about `days` ago.
+    fn assert_days_ago(value: &str, days: i64) {
+        let expected = Utc::now() - Duration::days(days);
+        let parsed = parse_date(value).unwrap();
+        assert!(
+            (expected - parsed).num_seconds().abs() < 60,
+            "{value} parsed to {parsed}, expected about {expected}"
+        );
+    }
+
+    #[test]
+    fn test_date_starts_or_ends_the_day() {
+        assert_eq!(
+            parse_date_bound("2024-01-31", false).unwrap().to_rfc3339(),
+            "2024-01-31T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_date_bound("2024-01-31", true).unwrap().to_rfc3339(),
+            "2024-01-31T23:59:59.999+00:00"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_is_kept_as_given() {
+        for end_of_day in [false, true] {
+            assert_eq!(
+                parse_date_bound("2024-01-31T10:30:00+02:00", end_of_day)
+                    .unwrap()
+                    .to_rfc3339(),
+                "2024-01-31T08:30:00+00:00"
+            );
+        }
+    }
+
+    #[test]
+    fn test_time_ago() {
+        assert_days_ago("30d", 30);
+        assert_days_ago("12w", 84);
+        assert_days_ago("6m", 180);
+        assert_days_ago("1y", 365);
+    }
+
+    #[test]
+    fn test_invalid_dates_are_rejected() {
+        for value in ["30x", "d", "", "2024-13-01", "-5d"] {
+            assert!(parse_date(value).is_err(), "{value} should be rejected");
+        }
+        // Counts too large for a date are an error, not a panic
+        assert!(parse_date("99999999999999999y").is_err());
+        assert!(parse_date("99999999999999999999d").is_err());
+        assert!(parse_date("999999999w").is_err());
+    }
+}
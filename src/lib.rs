@@ -1,8 +1,16 @@
 pub mod api;
 pub mod cli;
+pub mod markdown;
+pub mod matcher;
+pub mod query;
+pub mod score;
 pub mod search;
+pub mod teams;
+pub mod text;
 pub mod ui;
 
-pub use api::LinearClient;
+pub use api::{LinearClient, LinearClientBuilder};
 pub use cli::Args;
-pub use search::{search_issues, SearchOptions};
+pub use matcher::{MatchOptions, Matcher};
+pub use query::Query;
+pub use search::{search_issues, SearchOptions, SortOrder};
@@ -1,15 +1,64 @@
 use anyhow::Result;
 use clap::Parser;
-use linsearch::{search_issues, Args, LinearClient, SearchOptions};
+use std::collections::HashSet;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use linsearch::api::{IssueFilter, LinearError};
+use linsearch::markdown::MarkdownScope;
+use linsearch::query::Field;
+use linsearch::teams::{fetch_and_cache_teams, refresh_team_ids, resolve_team_ids, TeamCache};
+use linsearch::{search_issues, Args, LinearClient, MatchOptions, Matcher, Query, SearchOptions};
 
 mod ui {
     pub use linsearch::ui::*;
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let mut args = Args::parse();
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            ui::display_error(&err);
+            ExitCode::from(exit_code(&err))
+        }
+    }
+}
+
+/// Exit code for an error; Linear API errors each get their own code.
+fn exit_code(err: &anyhow::Error) -> u8 {
+    match err.downcast_ref::<LinearError>() {
+        Some(LinearError::Authentication(_)) => 3,
+        Some(LinearError::RateLimited { .. }) => 4,
+        Some(LinearError::NotFound(_)) => 5,
+        Some(LinearError::ComplexityExceeded(_)) => 6,
+        Some(LinearError::Network(_)) | Some(LinearError::Http(_)) => 7,
+        Some(LinearError::GraphQL(_)) => 8,
+        Some(LinearError::BudgetExhausted { .. }) => 9,
+        None => 1,
+    }
+}
+
+/// Parses one search term as a query, or as a single regex with `--regex`
+/// or a single literal string with `--literal`.
+fn parse_query(term: &str, regex: bool, literal: bool, options: MatchOptions) -> Result<Query> {
+    if regex {
+        return Ok(Query::term(term, Matcher::regex_with(term, options)?));
+    }
+    if literal {
+        return Ok(Query::term(term, Matcher::substring_with(term, options)));
+    }
+    Query::parse_with(term, options).map_err(|e| {
+        anyhow::anyhow!(
+            "Invalid query: {}\n  {}\n  {}^\nUse --literal to search for the term as written.",
+            e,
+            term,
+            " ".repeat(e.column - 1)
+        )
+    })
+}
 
+async fn run(mut args: Args) -> Result<()> {
     // Prompt for API key if not provided
     let api_key = match args.api_key {
         Some(key) => key,
@@ -24,66 +73,206 @@ async fn main() -> Result<()> {
         anyhow::bail!("API key is required");
     }
 
-    let client = LinearClient::new(api_key);
+    let mut search_terms = args.search_terms.clone();
+    if let Some(path) = &args.terms_file {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read terms file {}: {}", path.display(), e))?;
+        search_terms.extend(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(str::to_string),
+        );
+    }
+    let mut seen = HashSet::new();
+    search_terms.retain(|term| seen.insert(term.clone()));
+    if search_terms.is_empty() {
+        anyhow::bail!("No search terms given");
+    }
+    let search_term = search_terms.join(", ");
 
-    // Fetch and select team if not provided
-    let team_id = match args.team_id {
-        Some(id) => id,
-        None => {
-            ui::display_fetching_teams();
-            let teams = client.fetch_teams().await?;
+    // Parse the queries first so an invalid pattern fails before any request
+    let match_options = MatchOptions {
+        whole_word: args.word,
+        case_sensitive: args.case_sensitive,
+        fold_accents: args.fold_accents,
+        max_edits: args.fuzzy.unwrap_or(0),
+    };
+    let mut terms = Vec::with_capacity(search_terms.len());
+    for term in &search_terms {
+        let query = parse_query(term, args.regex, args.literal, match_options)?;
+        terms.push((term.clone(), query));
+    }
+    let uses_field = |field| terms.iter().any(|(_, query)| query.uses_field(field));
+
+    let team_cache = TeamCache::for_workspace(&args.endpoint, &api_key);
+
+    let client = LinearClient::builder()
+        .api_key(api_key)
+        .endpoint(args.endpoint.clone())
+        .timeout(Duration::from_secs(args.timeout))
+        .max_requests((args.max_requests > 0).then_some(args.max_requests))
+        .max_retries(args.max_retries)
+        .include_archived(args.include_archived)
+        .on_rate_limit_wait(ui::display_rate_limit_wait)
+        .build()?;
+
+    // Only the issues under --within are searched, whichever teams they are in
+    let subtree_ids = match &args.within {
+        Some(issue_id) => {
+            let subtree = client.fetch_subtree(issue_id).await?;
+            ui::display_subtree(&subtree);
+            Some(
+                subtree
+                    .into_iter()
+                    .map(|issue| issue.id)
+                    .collect::<Vec<_>>(),
+            )
+        }
+        None => None,
+    };
 
-            if teams.is_empty() {
-                anyhow::bail!("No teams found");
-            }
+    // Fetch and select teams if not provided; no team IDs means all teams
+    let mut teams_from_cache = false;
+    let team_ids = if args.all_teams || (subtree_ids.is_some() && args.teams.is_empty()) {
+        Vec::new()
+    } else if !args.teams.is_empty() {
+        let resolved = resolve_team_ids(&client, &args.teams, team_cache.as_ref()).await?;
+        teams_from_cache = resolved.from_cache;
+        resolved.ids
+    } else {
+        ui::display_fetching_teams();
+        let teams = fetch_and_cache_teams(&client, team_cache.as_ref()).await?;
+
+        if teams.is_empty() {
+            anyhow::bail!("No teams found");
+        }
 
-            ui::select_team_interactive(&teams)?
+        let selected = ui::select_teams_interactive(&teams)?;
+        if selected.is_empty() {
+            anyhow::bail!("Must select at least one team");
         }
+        selected
     };
 
+    args.comments |= args.all_locations;
+
     // Prompt for search options if not provided
     if !args.descriptions && !args.comments {
         args.descriptions = ui::confirm_search_descriptions()?;
         args.comments = ui::confirm_search_comments()?;
     }
 
-    if !args.descriptions && !args.comments {
+    // Qualified terms search their field even when it wasn't enabled, but
+    // unqualified terms are only searched where asked
+    let fetch_comments = uses_field(Field::Comment) || uses_field(Field::Author);
+    if !args.descriptions && !args.comments && !fetch_comments && !uses_field(Field::Description) {
         anyhow::bail!("Must search in at least descriptions or comments");
     }
 
     ui::display_search_info(
-        &args.search_term,
-        client.max_requests(),
+        &search_term,
+        client.fetch_rate_limit().await?,
         args.descriptions,
         args.comments,
     );
 
+    let markdown_scope = if args.raw_markdown {
+        MarkdownScope::Raw
+    } else if args.in_code {
+        MarkdownScope::Code
+    } else if args.prose_only {
+        MarkdownScope::Prose
+    } else {
+        MarkdownScope::Text
+    };
+
     ui::display_fetching_issues();
-    let issues = client.fetch_issues(&team_id).await?;
+    let mut search_options = SearchOptions::new(search_term, args.descriptions, args.comments)
+        .with_fetch_comments(fetch_comments)
+        .with_concurrency(args.concurrency)
+        .with_all_locations(args.all_locations)
+        .with_context(args.context)
+        .with_sort(args.sort)
+        .with_markdown(markdown_scope)
+        .with_sub_issues(args.rollup)
+        .with_comment_dates(
+            args.created_after.max(args.updated_since),
+            args.created_before,
+        );
+    search_options = match terms.len() {
+        1 => search_options.with_query(terms.remove(0).1),
+        _ => search_options.with_terms(terms),
+    };
+
+    let issue_filter = |team_ids: &[String]| -> Result<IssueFilter> {
+        let mut filter = IssueFilter::new()
+            .teams(team_ids)
+            .states(&args.states)
+            .labels(&args.labels)
+            .assignees(&args.assignees)
+            .priorities(&args.priorities)
+            .projects(&args.projects)
+            .cycles(&args.cycles)
+            .created_between(args.created_after, args.created_before)
+            .updated_since(args.updated_since);
+        if let Some(ids) = &subtree_ids {
+            filter = filter.ids(ids);
+        }
+        if args.server_side {
+            filter = search_options.push_down(filter).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "--server-side needs at least one term that every match must contain"
+                )
+            })?;
+        }
+        Ok(filter)
+    };
+    let inline_comments = ((args.comments || fetch_comments) && args.inline_comments > 0)
+        .then_some(args.inline_comments);
+    let mut issues = client
+        .fetch_filtered_issues(&issue_filter(&team_ids)?, inline_comments)
+        .await?;
+
+    // Cached teams may have been deleted or re-keyed since, so when they
+    // find nothing the teams are resolved again from Linear
+    if issues.is_empty() && teams_from_cache {
+        let fresh_ids = refresh_team_ids(&client, &args.teams, team_cache.as_ref()).await?;
+        if fresh_ids != team_ids {
+            issues = client
+                .fetch_filtered_issues(&issue_filter(&fresh_ids)?, inline_comments)
+                .await?;
+        }
+    }
 
-    let search_options =
-        SearchOptions::new(args.search_term.clone(), args.descriptions, args.comments);
     let matches = search_issues(&client, issues, &search_options).await?;
 
-    if client.request_count() >= client.max_requests() {
-        ui::display_rate_limit_warning(client.max_requests());
+    if let Some(max_requests) = client.max_requests() {
+        if client.budget_exhausted() {
+            ui::display_rate_limit_warning(max_requests);
+        }
     }
 
     // Output results - file by default, terminal if --terminal flag is set
     if args.terminal {
         ui::display_results(
             &matches,
-            &args.search_term,
+            &search_terms,
             client.request_count(),
             client.max_requests(),
+            client.rate_limit(),
+            args.rollup,
         );
     } else {
         ui::save_results_to_file(
             &args.output,
             &matches,
-            &args.search_term,
+            &search_terms,
             client.request_count(),
             client.max_requests(),
+            client.rate_limit(),
+            args.rollup,
         )?;
         ui::display_file_saved(&args.output, matches.len());
     }
@@ -0,0 +1,221 @@
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use crate::text::MappedText;
+
+/// Which parts of Markdown descriptions and comments are searched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MarkdownScope {
+    /// Prose, code and image alt text, but not link targets, bare URLs,
+    /// HTML, mention IDs or the file names Linear uses as alt text.
+    #[default]
+    Text,
+    /// Like `Text`, without inline code and code blocks.
+    Prose,
+    /// Inline code and code blocks only.
+    Code,
+    /// The Markdown source as is.
+    Raw,
+}
+
+/// Bare URLs and the UUIDs Linear uses for mentions, which are left out of
+/// prose.
+fn noise() -> &'static Regex {
+    static NOISE: OnceLock<Regex> = OnceLock::new();
+    NOISE.get_or_init(|| {
+        Regex::new(r"(?i)\b(?:https?://|www\.)\S+|\b[0-9a-f]{8}-(?:[0-9a-f]{4}-){3}[0-9a-f]{12}\b")
+            .expect("valid noise pattern")
+    })
+}
+
+/// Alt text Linear fills in for uploaded images: `image` or the file name.
+fn placeholder_alt() -> &'static Regex {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    PLACEHOLDER.get_or_init(|| {
+        Regex::new(r"(?i)^\s*(?:image|[^/\n]*\.(?:png|jpe?g|gif|webp|svg|heic))\s*$")
+            .expect("valid placeholder pattern")
+    })
+}
+
+/// The text of `markdown` that `scope` searches, mapped back to the source.
+/// Pieces of one block are joined as is, so a phrase can span formatting,
+/// while separate blocks are kept apart by a line break.
+pub fn searchable_text(markdown: &str, scope: MarkdownScope) -> MappedText {
+    if scope == MarkdownScope::Raw {
+        return MappedText::normalize(markdown, true, false);
+    }
+
+    let options =
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut text = MappedText::default();
+    let mut needs_break = false;
+    let push = |text: &mut MappedText, range: Range<usize>, needs_break: &mut bool| {
+        if range.is_empty() {
+            return;
+        }
+        if *needs_break && !text.text.is_empty() {
+            text.push_break(range.start);
+        }
+        *needs_break = false;
+        text.push_original(markdown, range);
+    };
+    // Keep what lies between URLs and mention IDs
+    let push_prose = |text: &mut MappedText, range: Range<usize>, needs_break: &mut bool| {
+        let mut start = range.start;
+        for noise in noise().find_iter(&markdown[range.clone()]) {
+            push(text, start..range.start + noise.start(), needs_break);
+            start = range.start + noise.end();
+        }
+        push(text, start..range.end, needs_break);
+    };
+
+    let wants_prose = scope != MarkdownScope::Code;
+    let wants_code = scope != MarkdownScope::Prose;
+    let mut in_code_block = false;
+    // Autolinks nest, so this counts depth
+    let mut hidden = 0usize;
+    // Pieces of the alt text of the image being read, kept once the whole
+    // of it is known not to be a placeholder
+    let mut image_alt: Option<Vec<Range<usize>>> = None;
+
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => {
+                in_code_block = false;
+                needs_break = true;
+            }
+            Event::Start(Tag::Image { .. }) => image_alt = Some(Vec::new()),
+            Event::End(TagEnd::Image) => {
+                let pieces = image_alt.take().unwrap_or_default();
+                let alt: String = pieces.iter().map(|r| &markdown[r.clone()]).collect();
+                if wants_prose && hidden == 0 && !placeholder_alt().is_match(&alt) {
+                    for piece in pieces {
+                        push_prose(&mut text, piece, &mut needs_break);
+                    }
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type: LinkType::Autolink | LinkType::Email,
+                ..
+            }) => hidden += 1,
+            Event::End(TagEnd::Link) if hidden > 0 => hidden -= 1,
+            Event::End(
+                TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Superscript
+                | TagEnd::Subscript
+                | TagEnd::Link,
+            ) => {}
+            Event::End(_) => needs_break = true,
+            Event::Text(_) | Event::Code(_) if image_alt.is_some() => {
+                if let Some(alt) = image_alt.as_mut() {
+                    alt.push(range);
+                }
+            }
+            Event::Text(_) if in_code_block && wants_code => {
+                push(&mut text, range, &mut needs_break);
+            }
+            Event::Text(_) if !in_code_block && hidden == 0 && wants_prose => {
+                push_prose(&mut text, range, &mut needs_break);
+            }
+            Event::SoftBreak | Event::HardBreak if wants_prose => {
+                push(&mut text, range, &mut needs_break);
+            }
+            Event::Code(_) if wants_code => {
+                // Drop the backticks around inline code
+                let source = &markdown[range.clone()];
+                let ticks = source.len() - source.trim_start_matches('`').len();
+                let inner = range.start + ticks..range.end.saturating_sub(ticks);
+                if scope == MarkdownScope::Code {
+                    needs_break = true;
+                }
+                push(&mut text, inner, &mut needs_break);
+            }
+            _ => {}
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(markdown: &str, scope: MarkdownScope) -> String {
+        searchable_text(markdown, scope).text
+    }
+
+    #[test]
+    fn test_image_keeps_alt_text_but_not_url() {
+        let markdown = "Before ![login form](https://uploads.linear.app/a/b.png) after";
+        assert_eq!(
+            text(markdown, MarkdownScope::Text),
+            "Before login form after"
+        );
+    }
+
+    #[test]
+    fn test_image_placeholder_alt_is_skipped() {
+        let markdown = "![image](https://uploads.linear.app/a/b.png)\n\n\
+                        ![Screenshot 2024-01-01.png](https://uploads.linear.app/c.png)";
+        assert_eq!(text(markdown, MarkdownScope::Text), "");
+    }
+
+    #[test]
+    fn test_link_keeps_text_but_not_target() {
+        let markdown = "See [the runbook](https://example.com/runbook) and <https://example.com>";
+        assert_eq!(text(markdown, MarkdownScope::Text), "See the runbook and ");
+    }
+
+    #[test]
+    fn test_bare_urls_and_uuids_are_removed() {
+        let markdown = "Logs at https://example.com/logs?id=7 from \
+                        1b4e28ba-2fa1-11d2-883f-0016d3cca427 today";
+        assert_eq!(text(markdown, MarkdownScope::Text), "Logs at  from  today");
+    }
+
+    #[test]
+    fn test_scopes() {
+        let markdown = "Call `retry()` here\n\n```\nbackoff();\n```\n\nDone";
+        assert_eq!(
+            text(markdown, MarkdownScope::Text),
+            "Call retry() here\nbackoff();\n\nDone"
+        );
+        assert_eq!(text(markdown, MarkdownScope::Prose), "Call  here\nDone");
+        assert_eq!(text(markdown, MarkdownScope::Code), "retry()\nbackoff();\n");
+        assert_eq!(text(markdown, MarkdownScope::Raw), markdown);
+    }
+
+    #[test]
+    fn test_blocks_are_kept_apart() {
+        assert_eq!(
+            text("# Kafka\n\nlag spikes", MarkdownScope::Text),
+            "Kafka\nlag spikes"
+        );
+    }
+
+    #[test]
+    fn test_offsets_map_back_to_markdown() {
+        let markdown = "Some **bold** [link](https://x.y) text";
+        let mapped = searchable_text(markdown, MarkdownScope::Text);
+        assert_eq!(mapped.text, "Some bold link text");
+
+        let start = mapped.text.find("bold").unwrap();
+        let bold = mapped.original_range(start..start + 4);
+        assert_eq!(&markdown[bold.clone()], "bold");
+
+        // A phrase across formatting covers the syntax in between
+        let start = mapped.text.find("bold link").unwrap();
+        let phrase = mapped.original_range(start..start + 9);
+        assert_eq!(&markdown[phrase], "bold** [link");
+
+        // Snippets of the original highlight the Markdown word
+        let snippet = &crate::text::snippets(markdown, &[bold], 60)[0];
+        assert_eq!(&snippet.text[snippet.highlights[0].clone()], "bold");
+    }
+}
@@ -0,0 +1,315 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::text::{typo_distance, MappedText};
+
+/// How a term is compared with text. The defaults ignore case but not
+/// accents, and match anywhere inside a word.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Only match at word boundaries, so `log` does not match `login`.
+    pub whole_word: bool,
+    /// Match letter case exactly.
+    pub case_sensitive: bool,
+    /// Ignore accents and other diacritics, so `cafe` matches `café`.
+    pub fold_accents: bool,
+    /// Typos allowed per word; above zero, substring terms match whole
+    /// words within this edit distance. Regexes ignore it.
+    pub max_edits: usize,
+}
+
+/// Decides whether a piece of text matches the search term, and where.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pattern: Pattern,
+    options: MatchOptions,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// Substring match; holds the term normalized like the text it is
+    /// compared with.
+    Substring(String),
+    Regex(Regex),
+    /// Typo-tolerant match of consecutive words; holds the normalized words
+    /// of the term.
+    Fuzzy(Vec<String>),
+}
+
+impl Matcher {
+    pub fn substring(term: &str) -> Self {
+        Self::substring_with(term, MatchOptions::default())
+    }
+
+    pub fn substring_with(term: &str, options: MatchOptions) -> Self {
+        let term = MappedText::normalize(term, options.case_sensitive, options.fold_accents).text;
+        let words: Vec<String> = term.unicode_words().map(str::to_string).collect();
+        let pattern = if options.max_edits > 0 && !words.is_empty() {
+            Pattern::Fuzzy(words)
+        } else {
+            Pattern::Substring(term)
+        };
+        Self { pattern, options }
+    }
+
+    /// Compiles `pattern`, failing up front if it is not a valid regex.
+    pub fn regex(pattern: &str) -> Result<Self> {
+        Self::regex_with(pattern, MatchOptions::default())
+    }
+
+    pub fn regex_with(pattern: &str, options: MatchOptions) -> Result<Self> {
+        // Case is left to the regex engine; accents are folded from both
+        // the pattern and the text
+        let pattern = MappedText::normalize(pattern, true, options.fold_accents).text;
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Invalid regular expression: {}", e))?;
+        Ok(Self {
+            pattern: Pattern::Regex(regex),
+            options,
+        })
+    }
+
+    /// Whether this matches a literal string, which Linear can also filter on.
+    pub fn is_literal(&self) -> bool {
+        matches!(self.pattern, Pattern::Substring(_))
+    }
+
+    pub fn options(&self) -> MatchOptions {
+        self.options
+    }
+
+    /// Byte ranges of every non-overlapping match in `text`.
+    pub fn find_spans(&self, text: &str) -> Vec<Range<usize>> {
+        let boundaries: Vec<usize> = if self.options.whole_word {
+            text.split_word_bound_indices()
+                .map(|(i, _)| i)
+                .chain(std::iter::once(text.len()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let accept = |range: &Range<usize>| {
+            !self.options.whole_word
+                || (boundaries.binary_search(&range.start).is_ok()
+                    && boundaries.binary_search(&range.end).is_ok())
+        };
+
+        match &self.pattern {
+            Pattern::Substring(term) => {
+                if term.is_empty() {
+                    return Vec::new();
+                }
+                let normalized = MappedText::normalize(
+                    text,
+                    self.options.case_sensitive,
+                    self.options.fold_accents,
+                );
+                let mut spans = Vec::new();
+                let mut start = 0;
+                while let Some(found) = normalized.text[start..].find(term.as_str()) {
+                    let found = start + found;
+                    let range = normalized.original_range(found..found + term.len());
+                    if accept(&range) {
+                        spans.push(range);
+                        start = found + term.len();
+                    } else {
+                        // Retry from the next character, in case a whole
+                        // word overlaps this rejected match
+                        let next = normalized.text[found..]
+                            .chars()
+                            .next()
+                            .map_or(1, char::len_utf8);
+                        start = found + next;
+                    }
+                }
+                spans
+            }
+            Pattern::Regex(regex) => {
+                let normalized = MappedText::normalize(text, true, self.options.fold_accents);
+                regex
+                    .find_iter(&normalized.text)
+                    .filter(|m| !m.is_empty())
+                    .map(|m| normalized.original_range(m.range()))
+                    .filter(|range| accept(range))
+                    .collect()
+            }
+            Pattern::Fuzzy(term_words) => self.find_fuzzy_spans(term_words, text),
+        }
+    }
+
+    /// Spans of runs of words in `text` that each are at most
+    /// `max_edits` typos away from the corresponding word of the term.
+    fn find_fuzzy_spans(&self, term_words: &[String], text: &str) -> Vec<Range<usize>> {
+        let normalized =
+            MappedText::normalize(text, self.options.case_sensitive, self.options.fold_accents);
+        let words: Vec<(usize, &str)> = normalized.text.unicode_word_indices().collect();
+
+        let mut spans = Vec::new();
+        let mut i = 0;
+        while i + term_words.len() <= words.len() {
+            let window = &words[i..i + term_words.len()];
+            let is_hit = window
+                .iter()
+                .zip(term_words)
+                .all(|((_, word), term)| self.within_typos(term, word));
+            if is_hit {
+                let (start, _) = window[0];
+                let (last_start, last) = window[window.len() - 1];
+                spans.push(normalized.original_range(start..last_start + last.len()));
+                i += term_words.len();
+            } else {
+                i += 1;
+            }
+        }
+        spans
+    }
+
+    fn within_typos(&self, term: &str, word: &str) -> bool {
+        // Never allow a whole short word to be replaced
+        let term_len = term.chars().count();
+        let allowed = self.options.max_edits.min(term_len.saturating_sub(1));
+        let word_len = word.chars().count();
+        word_len.abs_diff(term_len) <= allowed && typo_distance(term, word) <= allowed
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.find_spans(text).is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> MatchOptions {
+        MatchOptions::default()
+    }
+
+    fn whole_word() -> MatchOptions {
+        MatchOptions {
+            whole_word: true,
+            ..options()
+        }
+    }
+
+    /// The only span in `spans`.
+    fn single(spans: Vec<Range<usize>>) -> Range<usize> {
+        assert_eq!(spans.len(), 1, "expected one span, got {:?}", spans);
+        spans[0].clone()
+    }
+
+    fn texts<'a>(text: &'a str, spans: &[Range<usize>]) -> Vec<&'a str> {
+        spans.iter().map(|span| &text[span.clone()]).collect()
+    }
+
+    #[test]
+    fn test_substring_ignores_case() {
+        let matcher = Matcher::substring("kafka");
+        let text = "Kafka lag, KAFKA backlog";
+        assert_eq!(matcher.find_spans(text), [0..5, 11..16]);
+        assert!(!Matcher::substring_with(
+            "kafka",
+            MatchOptions {
+                case_sensitive: true,
+                ..options()
+            }
+        )
+        .is_match("KAFKA"));
+    }
+
+    #[test]
+    fn test_folded_spans_cover_original_characters() {
+        let matcher = Matcher::substring_with(
+            "cafe",
+            MatchOptions {
+                fold_accents: true,
+                ..options()
+            },
+        );
+        let text = "Un café, deux Cafés";
+        let spans = matcher.find_spans(text);
+        assert_eq!(spans, [3..8, 15..20]);
+        assert_eq!(texts(text, &spans), ["café", "Café"]);
+    }
+
+    #[test]
+    fn test_spans_around_growing_lowercase() {
+        let text = "İstanbul";
+        assert_eq!(single(Matcher::substring("stanbul").find_spans(text)), 2..9);
+        // The combining dot left by lowercasing `İ` splits `i` from `stanbul`
+        assert!(!Matcher::substring("istanbul").is_match(text));
+        let folded = Matcher::substring_with(
+            "istanbul",
+            MatchOptions {
+                fold_accents: true,
+                ..options()
+            },
+        );
+        assert_eq!(single(folded.find_spans(text)), 0..9);
+    }
+
+    #[test]
+    fn test_ligature_span() {
+        let matcher = Matcher::substring_with(
+            "file",
+            MatchOptions {
+                fold_accents: true,
+                ..options()
+            },
+        );
+        let text = "a ﬁle";
+        assert_eq!(texts(text, &matcher.find_spans(text)), ["ﬁle"]);
+    }
+
+    #[test]
+    fn test_whole_word_rejects_parts_of_words() {
+        let matcher = Matcher::substring_with("log", whole_word());
+        let text = "login log catalog Log.";
+        assert_eq!(matcher.find_spans(text), [6..9, 18..21]);
+    }
+
+    #[test]
+    fn test_whole_word_retries_after_rejection() {
+        // `a a` first matches at 1, inside `ba`, overlapping the match at 3
+        let matcher = Matcher::substring_with("a a", whole_word());
+        assert_eq!(single(matcher.find_spans("ba a a")), 3..6);
+    }
+
+    #[test]
+    fn test_whole_word_with_multibyte_characters() {
+        let matcher = Matcher::substring_with("café", whole_word());
+        let text = "cafés café";
+        assert_eq!(single(matcher.find_spans(text)), 7..12);
+    }
+
+    #[test]
+    fn test_regex_spans_map_to_original() {
+        let matcher = Matcher::regex_with(
+            r"caf.\b",
+            MatchOptions {
+                fold_accents: true,
+                ..options()
+            },
+        )
+        .unwrap();
+        let text = "Le Café";
+        assert_eq!(texts(text, &matcher.find_spans(text)), ["Café"]);
+    }
+
+    #[test]
+    fn test_fuzzy_matches_typos_per_word() {
+        let matcher = Matcher::substring_with(
+            "receive",
+            MatchOptions {
+                max_edits: 1,
+                ..options()
+            },
+        );
+        let text = "we recieve and reveice";
+        assert_eq!(texts(text, &matcher.find_spans(text)), ["recieve"]);
+    }
+}
@@ -0,0 +1,563 @@
+use std::fmt;
+
+use crate::matcher::{MatchOptions, Matcher};
+
+/// A parsed search query: terms combined with AND, OR and NOT.
+#[derive(Debug, Clone)]
+pub enum Query {
+    Term(Term),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+/// A single word or quoted phrase in a query, optionally restricted to one
+/// field with a qualifier such as `title:`.
+#[derive(Debug, Clone)]
+pub struct Term {
+    pub text: String,
+    pub field: Option<Field>,
+    pub matcher: Matcher,
+}
+
+impl Term {
+    pub fn new(text: &str, field: Option<Field>, options: MatchOptions) -> Self {
+        Self {
+            text: text.to_string(),
+            field,
+            matcher: Matcher::substring_with(text, options),
+        }
+    }
+}
+
+/// The field a qualified term must be found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// `title:`
+    Title,
+    /// `desc:` or `description:`
+    Description,
+    /// `comment:`, a comment body
+    Comment,
+    /// `author:`, the name of a commenter
+    Author,
+    /// `team:`, the issue's team key (exact, case-insensitive)
+    Team,
+}
+
+impl Field {
+    fn from_qualifier(qualifier: &str) -> Option<Self> {
+        match qualifier.to_lowercase().as_str() {
+            "title" => Some(Self::Title),
+            "desc" | "description" => Some(Self::Description),
+            "comment" | "comments" => Some(Self::Comment),
+            "author" | "commenter" => Some(Self::Author),
+            "team" => Some(Self::Team),
+            _ => None,
+        }
+    }
+}
+
+/// Why a query could not be parsed, with the 1-based column of the
+/// offending character.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Query {
+    /// Parses a query such as `kafka AND (lag OR backlog) NOT staging`.
+    /// Terms next to each other are implicitly AND'd, and `"..."` matches a
+    /// phrase. The keywords `AND`, `OR` and `NOT` must be uppercase.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        Self::parse_with(input, MatchOptions::default())
+    }
+
+    /// Like [`Query::parse`], matching every term with `options`.
+    pub fn parse_with(input: &str, options: MatchOptions) -> Result<Self, ParseError> {
+        let tokens = tokenize(input)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end_column: input.chars().count() + 1,
+            options,
+        };
+
+        if parser.tokens.is_empty() {
+            return Err(ParseError {
+                column: 1,
+                message: "empty query".to_string(),
+            });
+        }
+
+        let query = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(ParseError {
+                column: token.column,
+                message: format!("unexpected {}", token.kind),
+            });
+        }
+        Ok(query)
+    }
+
+    /// A query made of a single unqualified term matched with `matcher`.
+    pub fn term(text: &str, matcher: Matcher) -> Self {
+        Self::Term(Term {
+            text: text.to_string(),
+            field: None,
+            matcher,
+        })
+    }
+
+    /// Whether any term is qualified with `field`.
+    pub fn uses_field(&self, field: Field) -> bool {
+        self.terms().iter().any(|t| t.field == Some(field))
+    }
+
+    /// Evaluates the query, deciding each term with `is_hit`.
+    pub fn evaluate(&self, is_hit: &mut impl FnMut(&Term) -> bool) -> bool {
+        match self {
+            Self::Term(term) => is_hit(term),
+            Self::And(children) => children.iter().all(|c| c.evaluate(is_hit)),
+            Self::Or(children) => children.iter().any(|c| c.evaluate(is_hit)),
+            Self::Not(child) => !child.evaluate(is_hit),
+        }
+    }
+
+    /// Every term in the query, in order of appearance.
+    pub fn terms(&self) -> Vec<&Term> {
+        let mut terms = Vec::new();
+        self.collect_terms(&mut terms, true, false);
+        terms
+    }
+
+    /// Terms not under a NOT, i.e. the ones whose matches are worth showing.
+    pub fn positive_terms(&self) -> Vec<&Term> {
+        let mut terms = Vec::new();
+        self.collect_terms(&mut terms, false, false);
+        terms
+    }
+
+    fn collect_terms<'a>(&'a self, terms: &mut Vec<&'a Term>, negated_too: bool, negated: bool) {
+        match self {
+            Self::Term(term) => {
+                if negated_too || !negated {
+                    terms.push(term);
+                }
+            }
+            Self::And(children) | Self::Or(children) => {
+                for child in children {
+                    child.collect_terms(terms, negated_too, negated);
+                }
+            }
+            Self::Not(child) => child.collect_terms(terms, negated_too, !negated),
+        }
+    }
+
+    /// Whether finding more text can only turn a non-match into a match,
+    /// never the reverse (the query contains no NOT).
+    pub fn is_monotone(&self) -> bool {
+        match self {
+            Self::Term(_) => true,
+            Self::And(children) | Self::Or(children) => children.iter().all(Self::is_monotone),
+            Self::Not(_) => false,
+        }
+    }
+
+    /// Terms every matching issue must contain, usable to pre-filter issues.
+    pub fn required_terms(&self) -> Vec<&Term> {
+        match self {
+            Self::Term(term) => vec![term],
+            Self::And(children) => children.iter().flat_map(Self::required_terms).collect(),
+            Self::Or(_) | Self::Not(_) => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Word(Option<Field>, String),
+    Phrase(Option<Field>, String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word(_, word) => write!(f, "'{}'", word),
+            Self::Phrase(_, phrase) => write!(f, "\"{}\"", phrase),
+            Self::And => write!(f, "AND"),
+            Self::Or => write!(f, "OR"),
+            Self::Not => write!(f, "NOT"),
+            Self::Open => write!(f, "'('"),
+            Self::Close => write!(f, "')'"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    column: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let column = i + 1;
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push(Token {
+                    kind: TokenKind::Open,
+                    column,
+                });
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token {
+                    kind: TokenKind::Close,
+                    column,
+                });
+                i += 1;
+            }
+            '"' => {
+                let (phrase, len) = read_phrase(&chars[i..], column)?;
+                tokens.push(Token {
+                    kind: TokenKind::Phrase(None, phrase),
+                    column,
+                });
+                i += len;
+            }
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .position(|&c| c.is_whitespace() || matches!(c, '(' | ')' | '"'))
+                    .unwrap_or(chars.len() - i);
+                let word: String = chars[i..i + len].iter().collect();
+                i += len;
+
+                // `field:value` or `field:"a phrase"`; unknown prefixes such
+                // as `https:` are kept as plain words
+                let qualified = word.split_once(':').and_then(|(qualifier, value)| {
+                    Some((Field::from_qualifier(qualifier)?, value))
+                });
+                if let Some((field, value)) = qualified {
+                    if !value.is_empty() {
+                        tokens.push(Token {
+                            kind: TokenKind::Word(Some(field), value.to_string()),
+                            column,
+                        });
+                    } else if chars.get(i) == Some(&'"') {
+                        let (phrase, len) = read_phrase(&chars[i..], i + 1)?;
+                        tokens.push(Token {
+                            kind: TokenKind::Phrase(Some(field), phrase),
+                            column,
+                        });
+                        i += len;
+                    } else {
+                        return Err(ParseError {
+                            column,
+                            message: format!("expected a value after '{}'", word),
+                        });
+                    }
+                    continue;
+                }
+
+                let kind = match word.as_str() {
+                    "AND" => TokenKind::And,
+                    "OR" => TokenKind::Or,
+                    "NOT" => TokenKind::Not,
+                    _ => TokenKind::Word(None, word),
+                };
+                tokens.push(Token { kind, column });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a `"..."` phrase at the start of `chars`, returning its contents
+/// and the number of characters consumed including the quotes.
+fn read_phrase(chars: &[char], column: usize) -> Result<(String, usize), ParseError> {
+    let end = chars[1..]
+        .iter()
+        .position(|&c| c == '"')
+        .ok_or_else(|| ParseError {
+            column,
+            message: "unterminated phrase".to_string(),
+        })?;
+    let phrase: String = chars[1..1 + end].iter().collect();
+    if phrase.trim().is_empty() {
+        return Err(ParseError {
+            column,
+            message: "empty phrase".to_string(),
+        });
+    }
+    Ok((phrase, end + 2))
+}
+
+/// Recursive-descent parser. Precedence from loosest to tightest:
+/// OR, AND (explicit or implicit), NOT.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    end_column: usize,
+    options: MatchOptions,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Query, ParseError> {
+        let mut children = vec![self.parse_and()?];
+        while matches!(
+            self.peek(),
+            Some(Token {
+                kind: TokenKind::Or,
+                ..
+            })
+        ) {
+            self.pos += 1;
+            children.push(self.parse_and()?);
+        }
+        Ok(flatten(children, Query::Or))
+    }
+
+    fn parse_and(&mut self) -> Result<Query, ParseError> {
+        let mut children = vec![self.parse_unary()?];
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                Some(TokenKind::And) => {
+                    self.pos += 1;
+                    children.push(self.parse_unary()?);
+                }
+                // Implicit AND: another term follows directly
+                Some(
+                    TokenKind::Word(..) | TokenKind::Phrase(..) | TokenKind::Not | TokenKind::Open,
+                ) => {
+                    children.push(self.parse_unary()?);
+                }
+                _ => break,
+            }
+        }
+        Ok(flatten(children, Query::And))
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, ParseError> {
+        if matches!(
+            self.peek(),
+            Some(Token {
+                kind: TokenKind::Not,
+                ..
+            })
+        ) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Query, ParseError> {
+        let end_column = self.end_column;
+        let Some(token) = self.next() else {
+            return Err(ParseError {
+                column: end_column,
+                message: "expected a term".to_string(),
+            });
+        };
+
+        match token.kind {
+            TokenKind::Word(field, text) | TokenKind::Phrase(field, text) => {
+                Ok(Query::Term(Term::new(&text, field, self.options)))
+            }
+            TokenKind::Open => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some(Token {
+                        kind: TokenKind::Close,
+                        ..
+                    }) => Ok(query),
+                    Some(other) => Err(ParseError {
+                        column: other.column,
+                        message: format!("expected ')' but found {}", other.kind),
+                    }),
+                    None => Err(ParseError {
+                        column: token.column,
+                        message: "unclosed '('".to_string(),
+                    }),
+                }
+            }
+            other => Err(ParseError {
+                column: token.column,
+                message: format!("expected a term but found {}", other),
+            }),
+        }
+    }
+}
+
+/// Collapses a single-element AND/OR to its only child.
+fn flatten(mut children: Vec<Query>, combine: fn(Vec<Query>) -> Query) -> Query {
+    if children.len() == 1 {
+        children.remove(0)
+    } else {
+        combine(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders a query with explicit grouping, e.g. `(a AND (b OR c))`.
+    fn show(query: &Query) -> String {
+        let join = |children: &[Query], op: &str| {
+            let parts: Vec<String> = children.iter().map(show).collect();
+            format!("({})", parts.join(op))
+        };
+        match query {
+            Query::Term(term) => match term.field {
+                Some(field) => format!("{:?}:{}", field, term.text),
+                None => term.text.clone(),
+            },
+            Query::And(children) => join(children, " AND "),
+            Query::Or(children) => join(children, " OR "),
+            Query::Not(child) => format!("NOT {}", show(child)),
+        }
+    }
+
+    fn parse(input: &str) -> String {
+        show(&Query::parse(input).unwrap())
+    }
+
+    fn error(input: &str) -> ParseError {
+        Query::parse(input).unwrap_err()
+    }
+
+    #[test]
+    fn test_adjacent_terms_are_anded() {
+        assert_eq!(parse("kafka lag"), "(kafka AND lag)");
+        assert_eq!(
+            parse("kafka AND lag staging"),
+            "(kafka AND lag AND staging)"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(parse("a OR b c"), "(a OR (b AND c))");
+        assert_eq!(parse("a b OR c"), "((a AND b) OR c)");
+        assert_eq!(parse("NOT a b"), "(NOT a AND b)");
+        assert_eq!(parse("NOT (a OR b)"), "NOT (a OR b)");
+        assert_eq!(parse("a AND (b OR c) NOT d"), "(a AND (b OR c) AND NOT d)");
+    }
+
+    #[test]
+    fn test_lowercase_keywords_are_terms() {
+        assert_eq!(parse("and or not"), "(and AND or AND not)");
+    }
+
+    #[test]
+    fn test_phrases() {
+        assert_eq!(parse("\"repro steps\" crash"), "(repro steps AND crash)");
+        assert_eq!(parse("\"unwrap()\""), "unwrap()");
+        assert_eq!(parse("\"a OR b\""), "a OR b");
+    }
+
+    #[test]
+    fn test_qualifiers() {
+        assert_eq!(parse("title:crash"), "Title:crash");
+        assert_eq!(
+            parse("desc:a description:b"),
+            "(Description:a AND Description:b)"
+        );
+        assert_eq!(parse("comment:\"repro steps\""), "Comment:repro steps");
+        assert_eq!(
+            parse("author:alice team:ENG"),
+            "(Author:alice AND Team:ENG)"
+        );
+        // Unknown prefixes are part of the word
+        assert_eq!(parse("https://example.com"), "https://example.com");
+    }
+
+    #[test]
+    fn test_uses_field() {
+        let query = Query::parse("crash OR author:alice").unwrap();
+        assert!(query.uses_field(Field::Author));
+        assert!(!query.uses_field(Field::Title));
+    }
+
+    #[test]
+    fn test_required_terms() {
+        let texts = |input: &str| -> Vec<String> {
+            Query::parse(input)
+                .unwrap()
+                .required_terms()
+                .iter()
+                .map(|t| t.text.clone())
+                .collect()
+        };
+        assert_eq!(texts("a b"), ["a", "b"]);
+        assert_eq!(texts("a (b OR c)"), ["a"]);
+        assert_eq!(texts("a NOT b"), ["a"]);
+        assert!(texts("a OR b").is_empty());
+    }
+
+    #[test]
+    fn test_is_monotone() {
+        assert!(Query::parse("a (b OR c)").unwrap().is_monotone());
+        assert!(!Query::parse("a NOT b").unwrap().is_monotone());
+        assert!(!Query::parse("a OR (b NOT c)").unwrap().is_monotone());
+    }
+
+    #[test]
+    fn test_positive_terms_skip_negated() {
+        let query = Query::parse("a NOT (b NOT c)").unwrap();
+        let positive: Vec<&str> = query
+            .positive_terms()
+            .iter()
+            .map(|t| t.text.as_str())
+            .collect();
+        assert_eq!(positive, ["a", "c"]);
+        assert_eq!(query.terms().len(), 3);
+    }
+
+    #[test]
+    fn test_error_columns() {
+        assert_eq!(error("").message, "empty query");
+        assert_eq!(error("a OR").column, 5);
+        assert_eq!(error("a )").column, 3);
+        assert_eq!(error("(a b").column, 1);
+        assert_eq!(error("a \"b").column, 3);
+        assert_eq!(error("title: x").column, 1);
+        assert_eq!(error("a \"  \"").message, "empty phrase");
+        assert_eq!(error("unwrap()").column, 8);
+        // Columns count characters, not bytes
+        assert_eq!(error("café )").column, 6);
+    }
+}
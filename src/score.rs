@@ -0,0 +1,217 @@
+use chrono::{DateTime, Utc};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::query::{Field, Term};
+
+/// Weight of a match in each part of an issue, relative to a comment.
+const TITLE_BOOST: f64 = 3.0;
+const DESCRIPTION_BOOST: f64 = 1.5;
+const COMMENTS_BOOST: f64 = 1.0;
+
+/// BM25 term frequency saturation and length normalization.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// How much more a just-updated issue scores than an equally relevant stale
+/// one, and how many days it takes for that bonus to halve.
+const RECENCY_WEIGHT: f64 = 0.5;
+const RECENCY_HALF_LIFE_DAYS: f64 = 90.0;
+
+/// The searched text of an issue.
+pub struct Document<'a> {
+    pub title: &'a str,
+    /// Empty when descriptions are not searched.
+    pub description: &'a str,
+    /// Empty when comments are not searched.
+    pub comments: Vec<&'a str>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Part {
+    Title,
+    Description,
+    Comments,
+}
+
+const PARTS: [Part; 3] = [Part::Title, Part::Description, Part::Comments];
+
+impl Part {
+    fn boost(self) -> f64 {
+        match self {
+            Self::Title => TITLE_BOOST,
+            Self::Description => DESCRIPTION_BOOST,
+            Self::Comments => COMMENTS_BOOST,
+        }
+    }
+
+    /// Whether `term` is looked for in this part.
+    fn applies_to(self, term: &Term) -> bool {
+        match term.field {
+            None => true,
+            Some(Field::Title) => matches!(self, Self::Title),
+            Some(Field::Description) => matches!(self, Self::Description),
+            Some(Field::Comment) => matches!(self, Self::Comments),
+            Some(Field::Author | Field::Team) => false,
+        }
+    }
+
+    fn texts<'a>(self, document: &'a Document) -> Vec<&'a str> {
+        match self {
+            Self::Title => vec![document.title],
+            Self::Description => vec![document.description],
+            Self::Comments => document.comments.clone(),
+        }
+    }
+}
+
+/// BM25F-style relevance of issues to the positive terms of a query, with
+/// document frequencies and average lengths taken from the searched issues,
+/// scaled up for recently updated issues.
+pub struct Scorer<'q> {
+    terms: Vec<&'q Term>,
+    idf: Vec<f64>,
+    average_lengths: [f64; 3],
+    now: DateTime<Utc>,
+}
+
+impl<'q> Scorer<'q> {
+    pub fn new(terms: Vec<&'q Term>, documents: &[Document]) -> Self {
+        let count = documents.len() as f64;
+
+        let mut average_lengths = [0.0; 3];
+        for (i, part) in PARTS.iter().enumerate() {
+            let total: usize = documents.iter().map(|d| part_length(d, *part)).sum();
+            average_lengths[i] = (total as f64 / count.max(1.0)).max(1.0);
+        }
+
+        let idf = terms
+            .iter()
+            .map(|term| {
+                let containing = documents
+                    .iter()
+                    .filter(|d| PARTS.iter().any(|p| term_frequency(term, d, *p) > 0))
+                    .count() as f64;
+                (1.0 + (count - containing + 0.5) / (containing + 0.5)).ln()
+            })
+            .collect();
+
+        Self {
+            terms,
+            idf,
+            average_lengths,
+            now: Utc::now(),
+        }
+    }
+
+    pub fn score(&self, document: &Document) -> f64 {
+        let relevance: f64 = self
+            .terms
+            .iter()
+            .zip(&self.idf)
+            .map(|(term, idf)| {
+                // Field-weighted frequency, saturated once across all fields
+                let frequency: f64 = PARTS
+                    .iter()
+                    .enumerate()
+                    .map(|(i, part)| {
+                        let tf = term_frequency(term, document, *part) as f64;
+                        let length = part_length(document, *part) as f64;
+                        let norm = 1.0 - B + B * length / self.average_lengths[i];
+                        part.boost() * tf / norm
+                    })
+                    .sum();
+                idf * frequency * (K1 + 1.0) / (frequency + K1)
+            })
+            .sum();
+
+        let age_days = (self.now - document.updated_at).num_seconds().max(0) as f64 / 86_400.0;
+        let recency = 0.5_f64.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+        relevance * (1.0 + RECENCY_WEIGHT * recency)
+    }
+}
+
+fn term_frequency(term: &Term, document: &Document, part: Part) -> usize {
+    if !part.applies_to(term) {
+        return 0;
+    }
+    part.texts(document)
+        .iter()
+        .map(|text| term.matcher.find_spans(text).len())
+        .sum()
+}
+
+/// Length of a part in words.
+fn part_length(document: &Document, part: Part) -> usize {
+    part.texts(document)
+        .iter()
+        .map(|text| text.unicode_words().count())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Query;
+    use chrono::Duration;
+
+    fn document<'a>(
+        title: &'a str,
+        description: &'a str,
+        comment: &'a str,
+        updated_at: DateTime<Utc>,
+    ) -> Document<'a> {
+        Document {
+            title,
+            description,
+            comments: vec![comment],
+            updated_at,
+        }
+    }
+
+    /// Scores of `documents` for `query`, in order.
+    fn scores(query: &str, documents: &[Document]) -> Vec<f64> {
+        let query = Query::parse(query).unwrap();
+        let scorer = Scorer::new(query.positive_terms(), documents);
+        documents.iter().map(|d| scorer.score(d)).collect()
+    }
+
+    #[test]
+    fn test_title_beats_description_beats_comment() {
+        let now = Utc::now();
+        // Parts have the same length everywhere, so only the boosts differ
+        let documents = [
+            document("crash report", "login happens", "login seen", now),
+            document("login report", "crash happens", "login seen", now),
+            document("login report", "login happens", "crash seen", now),
+            document("login report", "login happens", "login seen", now),
+        ];
+        let scores = scores("crash", &documents);
+        assert!(scores[0] > scores[1], "{scores:?}");
+        assert!(scores[1] > scores[2], "{scores:?}");
+        assert!(scores[2] > 0.0, "{scores:?}");
+    }
+
+    #[test]
+    fn test_recent_issue_ranks_first() {
+        let now = Utc::now();
+        let documents = [
+            document("crash report", "", "", now - Duration::days(200)),
+            document("crash report", "", "", now - Duration::days(1)),
+            document("login report", "", "", now),
+        ];
+        let scores = scores("crash", &documents);
+        assert!(scores[1] > scores[0], "{scores:?}");
+    }
+
+    #[test]
+    fn test_no_matching_terms_scores_zero() {
+        let now = Utc::now();
+        let documents = [
+            document("crash report", "", "", now),
+            document("login report", "slow login", "", now),
+        ];
+        assert_eq!(scores("crash", &documents)[1], 0.0);
+        assert_eq!(scores("title:login", &documents)[0], 0.0);
+    }
+}
@@ -1,22 +1,240 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use std::ops::Range;
 
-use crate::api::types::{CommentHit, Issue, Match};
-use crate::api::LinearClient;
+use crate::api::types::{Comment, CommentHit, Issue, Match};
+use crate::api::{IssueFilter, LinearClient, LinearError};
+use crate::markdown::{searchable_text, MarkdownScope};
+use crate::matcher::Matcher;
+use crate::query::{Field, Query, Term};
+use crate::score::{Document, Scorer};
+use crate::text::{snippets, MappedText};
+
+/// Default number of comment batch requests in flight at once.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Default characters of context shown on each side of a match.
+pub const DEFAULT_CONTEXT: usize = 60;
+
+/// Matches whose sub-issues are listed by one query.
+const SUB_ISSUE_PARENTS_PER_QUERY: usize = 100;
+
+/// Order in which matches are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortOrder {
+    /// Highest score first
+    #[default]
+    Relevance,
+    /// Most recently updated first
+    Updated,
+    /// Most recently created first
+    Created,
+    /// By team key, then by score
+    Team,
+}
 
 pub struct SearchOptions {
     pub search_term: String,
     pub search_descriptions: bool,
     pub search_comments: bool,
+    /// Fetch comments for `comment:` and `author:` terms even when
+    /// unqualified terms aren't searched in comments.
+    pub fetch_comments: bool,
+    pub concurrency: usize,
+    /// Gather comment hits even for issues already matched by their title
+    /// or description, at the cost of fetching their comments.
+    pub all_locations: bool,
+    /// Characters of context kept around each match in snippets.
+    pub context: usize,
+    pub sort: SortOrder,
+    /// Which parts of Markdown descriptions and comments are searched.
+    pub markdown: MarkdownScope,
+    /// List the sub-issues of each match, at the cost of extra requests.
+    pub sub_issues: bool,
+    /// Only comments created within these inclusive bounds are searched.
+    pub comments_after: Option<DateTime<Utc>>,
+    pub comments_before: Option<DateTime<Utc>>,
+    pub query: Query,
+    /// When searching for several terms at once, each term and its query;
+    /// `query` is then any of them.
+    pub terms: Vec<(String, Query)>,
 }
 
 impl SearchOptions {
     pub fn new(search_term: String, search_descriptions: bool, search_comments: bool) -> Self {
         Self {
-            search_term,
             search_descriptions,
             search_comments,
+            fetch_comments: false,
+            concurrency: DEFAULT_CONCURRENCY,
+            all_locations: false,
+            context: DEFAULT_CONTEXT,
+            sort: SortOrder::default(),
+            markdown: MarkdownScope::default(),
+            sub_issues: false,
+            comments_after: None,
+            comments_before: None,
+            query: Query::term(&search_term, Matcher::substring(&search_term)),
+            search_term,
+            terms: Vec::new(),
         }
     }
+
+    /// Evaluates `query` per issue instead of the literal search term.
+    pub fn with_query(mut self, query: Query) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Only searches comments created within the given inclusive bounds.
+    pub fn with_comment_dates(
+        mut self,
+        after: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.comments_after = after;
+        self.comments_before = before;
+        self
+    }
+
+    /// Searches for several terms in one pass: an issue matches when any of
+    /// them does, and each match records which ones.
+    pub fn with_terms(mut self, terms: Vec<(String, Query)>) -> Self {
+        self.query = Query::Or(terms.iter().map(|(_, query)| query.clone()).collect());
+        self.terms = terms;
+        self
+    }
+
+    /// Adds the terms every match must contain to `filter`, so Linear only
+    /// returns candidate issues. Returns `None` if no such term exists.
+    pub fn push_down(&self, mut filter: IssueFilter) -> Option<IssueFilter> {
+        let required: Vec<&Term> = self
+            .query
+            .required_terms()
+            .into_iter()
+            .filter(|t| t.matcher.is_literal())
+            .collect();
+        if required.is_empty() {
+            return None;
+        }
+
+        for term in required {
+            filter = filter.ignoring_accents(term.matcher.options().fold_accents);
+            filter = match term.field {
+                None => {
+                    filter.containing(&term.text, self.search_descriptions, self.search_comments)
+                }
+                Some(Field::Title) => filter.title_containing(&term.text),
+                Some(Field::Description) => filter.description_containing(&term.text),
+                Some(Field::Comment) => filter.comment_containing(&term.text),
+                Some(Field::Author) => filter.commented_by(&term.text),
+                Some(Field::Team) => filter.team_key(&term.text),
+            };
+        }
+        Some(filter)
+    }
+
+    /// Fetches comments for qualified terms without searching them for
+    /// unqualified ones.
+    pub fn with_fetch_comments(mut self, fetch_comments: bool) -> Self {
+        self.fetch_comments = fetch_comments;
+        self
+    }
+
+    /// Reports every matching comment, instead of skipping the comments of
+    /// issues that already matched.
+    pub fn with_all_locations(mut self, all_locations: bool) -> Self {
+        self.all_locations = all_locations;
+        self
+    }
+
+    /// Sets how many characters of context snippets show around a match.
+    pub fn with_context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+
+    pub fn with_sort(mut self, sort: SortOrder) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn with_markdown(mut self, markdown: MarkdownScope) -> Self {
+        self.markdown = markdown;
+        self
+    }
+
+    /// Lists the sub-issues of each match in [`Match::children`].
+    pub fn with_sub_issues(mut self, sub_issues: bool) -> Self {
+        self.sub_issues = sub_issues;
+        self
+    }
+
+    /// Sets how many comment requests may be in flight at once.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+}
+
+/// An issue being searched, with the comments gathered for it so far.
+struct Candidate {
+    issue: Issue,
+    /// Whether the query holds on the title and description alone.
+    matched_issue: bool,
+    /// The searched part of the description.
+    description: Option<MappedText>,
+    comments: Vec<Comment>,
+    /// The searched part of each comment body, filled in once all comments
+    /// are fetched.
+    comment_texts: Vec<MappedText>,
+}
+
+impl Candidate {
+    /// Whether `term` is found in the fields it applies to. Unqualified
+    /// terms only look in the descriptions and comments being searched, and
+    /// comments are only looked at when `with_comments` is set.
+    fn has_term(&self, term: &Term, options: &SearchOptions, with_comments: bool) -> bool {
+        let matcher = &term.matcher;
+        let in_title = || matcher.is_match(&self.issue.title);
+        let in_desc = |scoped: bool| {
+            self.description
+                .as_ref()
+                .filter(|_| scoped || options.search_descriptions)
+                .is_some_and(|d| matcher.is_match(&d.text))
+        };
+        let in_comments = |scoped: bool| {
+            with_comments
+                && (scoped || options.search_comments)
+                && self
+                    .comment_texts
+                    .iter()
+                    .any(|text| matcher.is_match(&text.text))
+        };
+
+        match term.field {
+            None => in_title() || in_desc(false) || in_comments(false),
+            Some(Field::Title) => in_title(),
+            Some(Field::Description) => in_desc(true),
+            Some(Field::Comment) => in_comments(true),
+            Some(Field::Author) => {
+                with_comments && self.comments.iter().any(|c| is_author(term, c))
+            }
+            Some(Field::Team) => self
+                .issue
+                .team
+                .as_ref()
+                .is_some_and(|t| t.key.eq_ignore_ascii_case(&term.text)),
+        }
+    }
+}
+
+fn is_author(term: &Term, comment: &Comment) -> bool {
+    comment
+        .user
+        .as_ref()
+        .is_some_and(|u| term.matcher.is_match(&u.name))
 }
 
 pub async fn search_issues(
@@ -24,59 +242,417 @@ pub async fn search_issues(
     issues: Vec<Issue>,
     options: &SearchOptions,
 ) -> Result<Vec<Match>> {
-    let mut matches = Vec::new();
-    let search_term_lower = options.search_term.to_lowercase();
+    let query = &options.query;
 
-    for issue in issues {
-        if client.request_count() >= client.max_requests() {
-            break;
-        }
-
-        let in_title = issue.title.to_lowercase().contains(&search_term_lower);
-        let in_desc = options.search_descriptions
-            && issue
+    let mut candidates: Vec<Candidate> = issues
+        .into_iter()
+        .map(|issue| {
+            let description = issue
                 .description
-                .as_ref()
-                .map(|d| d.to_lowercase().contains(&search_term_lower))
-                .unwrap_or(false);
-
-        let mut comment_hits = Vec::new();
-
-        if options.search_comments && !in_title && !in_desc {
-            if let Ok(comments) = client.fetch_comments(&issue.id).await {
-                for comment in comments {
-                    if comment.body.to_lowercase().contains(&search_term_lower) {
-                        comment_hits.push(CommentHit {
-                            commenter: comment
-                                .user
-                                .map(|u| u.name)
-                                .unwrap_or_else(|| "Unknown".to_string()),
-                            created_at: comment.created_at,
-                            url: comment.url,
-                        });
+                .as_deref()
+                .map(|d| searchable_text(d, options.markdown));
+            let mut candidate = Candidate {
+                issue,
+                matched_issue: false,
+                description,
+                comments: Vec::new(),
+                comment_texts: Vec::new(),
+            };
+            candidate.matched_issue =
+                query.evaluate(&mut |term| candidate.has_term(term, options, false));
+            candidate
+        })
+        .collect();
+
+    // Issues whose comments still have to be requested, with the cursor to
+    // continue after when a first page came inline with the issue.
+    let mut to_fetch: Vec<(usize, (String, Option<String>))> = Vec::new();
+
+    if options.search_comments || options.fetch_comments {
+        for (idx, candidate) in candidates.iter_mut().enumerate() {
+            // Comments can't undo a match unless the query has a NOT, so by
+            // default they are only fetched to find more matching issues, or
+            // more of the terms when searching for several
+            if candidate.matched_issue
+                && query.is_monotone()
+                && !options.all_locations
+                && options.terms.iter().all(|(_, term_query)| {
+                    term_query.evaluate(&mut |term| candidate.has_term(term, options, false))
+                })
+            {
+                continue;
+            }
+            match candidate.issue.comments.take() {
+                Some(inline) => {
+                    candidate.comments = inline.nodes;
+                    if inline.page_info.has_next_page {
+                        let cursor = inline.page_info.end_cursor;
+                        to_fetch.push((idx, (candidate.issue.id.clone(), cursor)));
                     }
                 }
+                None => to_fetch.push((idx, (candidate.issue.id.clone(), None))),
             }
         }
+    }
 
-        if in_title || in_desc || !comment_hits.is_empty() {
-            let team_str = issue
-                .team
-                .as_ref()
-                .map(|t| format!("[{}] {}", t.key, t.name))
-                .unwrap_or_else(|| "Unknown".to_string());
-
-            matches.push(Match {
-                team: team_str,
-                id: issue.identifier,
-                title: issue.title,
-                url: issue.url,
-                in_title,
-                in_desc,
-                comments_matched: comment_hits,
-            });
+    // Batches run concurrently but are yielded in order, so the first batch
+    // that found the request budget spent marks where results are cut off.
+    let batch_size = client.comments_batch_size();
+    let mut batches = stream::iter(to_fetch.chunks(batch_size))
+        .map(|chunk| async move {
+            let requests: Vec<(String, Option<String>)> =
+                chunk.iter().map(|(_, request)| request.clone()).collect();
+            (chunk, client.fetch_comments_batch(&requests).await)
+        })
+        .buffered(options.concurrency.max(1));
+
+    let mut cutoff = candidates.len();
+    while let Some((chunk, fetched)) = batches.next().await {
+        match fetched {
+            Ok(fetched) => {
+                for ((idx, _), comments) in chunk.iter().zip(fetched) {
+                    candidates[*idx].comments.extend(comments);
+                }
+            }
+            Err(LinearError::BudgetExhausted { .. }) => {
+                cutoff = chunk[0].0;
+                break;
+            }
+            Err(e) => return Err(e.into()),
         }
     }
+    drop(batches);
+    candidates.truncate(cutoff);
+    for candidate in &mut candidates {
+        candidate.comments.retain(|c| {
+            options
+                .comments_after
+                .is_none_or(|after| c.created_at >= after)
+                && options
+                    .comments_before
+                    .is_none_or(|before| c.created_at <= before)
+        });
+        candidate.comment_texts = candidate
+            .comments
+            .iter()
+            .map(|c| searchable_text(&c.body, options.markdown))
+            .collect();
+    }
+
+    // Scores are relative to every searched issue, not just the matches
+    let search_descriptions = options.search_descriptions || query.uses_field(Field::Description);
+    let documents: Vec<Document> = candidates
+        .iter()
+        .map(|candidate| Document {
+            title: &candidate.issue.title,
+            description: candidate
+                .description
+                .as_ref()
+                .filter(|_| search_descriptions)
+                .map_or("", |d| d.text.as_str()),
+            comments: candidate
+                .comment_texts
+                .iter()
+                .map(|t| t.text.as_str())
+                .collect(),
+            updated_at: candidate.issue.updated_at,
+        })
+        .collect();
+    let scorer = Scorer::new(query.positive_terms(), &documents);
+    let scores: Vec<f64> = documents.iter().map(|d| scorer.score(d)).collect();
+    drop(documents);
+
+    let (issue_ids, mut matches): (Vec<String>, Vec<Match>) = candidates
+        .into_iter()
+        .zip(scores)
+        .filter_map(|(candidate, score)| {
+            let issue_id = candidate.issue.id.clone();
+            build_match(candidate, score, options).map(|m| (issue_id, m))
+        })
+        .unzip();
+    if options.sub_issues {
+        add_sub_issues(client, &issue_ids, &mut matches).await?;
+    }
+    sort_matches(&mut matches, options.sort);
 
     Ok(matches)
 }
+
+/// Lists the sub-issues of each match, given the issue IDs of the matches.
+/// Sub-issues cost too much to select with every issue of a page, so only
+/// matches get them; once the request budget is spent the rest get none.
+async fn add_sub_issues(
+    client: &LinearClient,
+    issue_ids: &[String],
+    matches: &mut [Match],
+) -> Result<()> {
+    for (ids, chunk) in issue_ids
+        .chunks(SUB_ISSUE_PARENTS_PER_QUERY)
+        .zip(matches.chunks_mut(SUB_ISSUE_PARENTS_PER_QUERY))
+    {
+        let sub_issues = match client.fetch_sub_issues(ids).await {
+            Ok(sub_issues) => sub_issues,
+            Err(LinearError::BudgetExhausted { .. }) => break,
+            Err(e) => return Err(e.into()),
+        };
+        for sub_issue in sub_issues {
+            let parent = sub_issue
+                .parent
+                .and_then(|parent| ids.iter().position(|id| *id == parent.id));
+            if let Some(idx) = parent {
+                chunk[idx].children.push(sub_issue.issue.identifier);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sorts `matches` in `order`; ties keep their existing order.
+pub fn sort_matches(matches: &mut [Match], order: SortOrder) {
+    match order {
+        SortOrder::Relevance => matches.sort_by(|a, b| b.score.total_cmp(&a.score)),
+        SortOrder::Updated => matches.sort_by_key(|m| std::cmp::Reverse(m.updated_at)),
+        SortOrder::Created => matches.sort_by_key(|m| std::cmp::Reverse(m.created_at)),
+        SortOrder::Team => {
+            matches.sort_by(|a, b| a.team.cmp(&b.team).then(b.score.total_cmp(&a.score)))
+        }
+    }
+}
+
+fn build_match(candidate: Candidate, score: f64, options: &SearchOptions) -> Option<Match> {
+    let query = &options.query;
+
+    let matched = candidate.matched_issue && query.is_monotone()
+        || query.evaluate(&mut |term| candidate.has_term(term, options, true));
+    if !matched {
+        return None;
+    }
+    let matched_terms: Vec<String> = options
+        .terms
+        .iter()
+        .filter(|(_, term_query)| {
+            term_query.evaluate(&mut |term| candidate.has_term(term, options, true))
+        })
+        .map(|(label, _)| label.clone())
+        .collect();
+
+    // Only terms outside a NOT are highlighted and count as comment hits
+    let terms = query.positive_terms();
+    let in_field = |field: Field, unqualified: bool| -> Vec<&Term> {
+        terms
+            .iter()
+            .copied()
+            .filter(|t| t.field.map_or(unqualified, |f| f == field))
+            .collect()
+    };
+    let title_terms = in_field(Field::Title, true);
+    let desc_terms = in_field(Field::Description, options.search_descriptions);
+    let body_terms = in_field(Field::Comment, options.search_comments);
+    let author_terms: Vec<&Term> = terms
+        .iter()
+        .copied()
+        .filter(|t| t.field == Some(Field::Author))
+        .collect();
+
+    let title_spans = find_spans(&title_terms, &candidate.issue.title);
+    let desc_spans = candidate
+        .description
+        .as_ref()
+        .filter(|_| options.search_descriptions || query.uses_field(Field::Description))
+        .map(|d| find_original_spans(&desc_terms, d))
+        .unwrap_or_default();
+
+    let mut matched_words = Vec::new();
+    collect_words(&mut matched_words, &candidate.issue.title, &title_spans);
+    let mut desc_snippets = Vec::new();
+    if let Some(description) = &candidate.issue.description {
+        collect_words(&mut matched_words, description, &desc_spans);
+        desc_snippets = snippets(description, &desc_spans, options.context);
+    }
+
+    let Candidate {
+        issue,
+        comments,
+        comment_texts,
+        ..
+    } = candidate;
+
+    // A comment is a hit when it contains a body term and, if the query
+    // names authors, was written by one of them
+    let comment_hits: Vec<CommentHit> = comments
+        .into_iter()
+        .zip(comment_texts)
+        .filter_map(|(comment, text)| {
+            let spans = find_original_spans(&body_terms, &text);
+            let body_hit = !spans.is_empty();
+            let author_hit = author_terms.iter().any(|t| is_author(t, &comment));
+            let hit = match (body_terms.is_empty(), author_terms.is_empty()) {
+                (false, false) => body_hit && author_hit,
+                (false, true) => body_hit,
+                (true, false) => author_hit,
+                (true, true) => false,
+            };
+            if !hit {
+                return None;
+            }
+            collect_words(&mut matched_words, &comment.body, &spans);
+            let snippets = snippets(&comment.body, &spans, options.context);
+            Some(CommentHit {
+                commenter: comment
+                    .user
+                    .map(|u| u.name)
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                created_at: comment.created_at,
+                url: comment.url,
+                spans,
+                snippets,
+            })
+        })
+        .collect();
+
+    let team_str = issue
+        .team
+        .as_ref()
+        .map(|t| format!("[{}] {}", t.key, t.name))
+        .unwrap_or_else(|| "Unknown".to_string());
+
+    Some(Match {
+        team: team_str,
+        id: issue.identifier,
+        title: issue.title,
+        url: issue.url,
+        created_at: issue.created_at,
+        updated_at: issue.updated_at,
+        details: issue.details,
+        parent: issue.parent,
+        children: Vec::new(),
+        score,
+        in_title: !title_spans.is_empty(),
+        in_desc: !desc_spans.is_empty(),
+        title_spans,
+        desc_spans,
+        desc_snippets,
+        comments_matched: comment_hits,
+        matched_words,
+        matched_terms,
+    })
+}
+
+/// Adds the text of each span to `words`, skipping case-insensitive
+/// duplicates.
+fn collect_words(words: &mut Vec<String>, text: &str, spans: &[Range<usize>]) {
+    for span in spans {
+        let word = &text[span.clone()];
+        if !words
+            .iter()
+            .any(|w| w.to_lowercase() == word.to_lowercase())
+        {
+            words.push(word.to_string());
+        }
+    }
+}
+
+/// Like [`find_spans`] on searchable text, with the spans mapped back to
+/// the original text.
+fn find_original_spans(terms: &[&Term], text: &MappedText) -> Vec<Range<usize>> {
+    find_spans(terms, &text.text)
+        .into_iter()
+        .map(|span| text.original_range(span))
+        .collect()
+}
+
+/// Spans of all `terms` in `text`, sorted with overlapping spans merged.
+fn find_spans(terms: &[&Term], text: &str) -> Vec<Range<usize>> {
+    let mut spans: Vec<Range<usize>> = terms
+        .iter()
+        .flat_map(|term| term.matcher.find_spans(text))
+        .collect();
+    spans.sort_by_key(|span| (span.start, span.end));
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(title: &str, description: &str, comments: &[&str]) -> Candidate {
+        let issue: Issue = serde_json::from_value(serde_json::json!({
+            "id": "issue-1",
+            "identifier": "ENG-1",
+            "title": title,
+            "url": "https://linear.app/eng/issue/ENG-1",
+            "description": description,
+            "createdAt": "2024-01-01T00:00:00Z",
+            "updatedAt": "2024-01-02T00:00:00Z",
+            "team": null,
+            "parent": null,
+        }))
+        .unwrap();
+        let comments: Vec<Comment> = comments
+            .iter()
+            .enumerate()
+            .map(|(i, body)| Comment {
+                id: format!("comment-{i}"),
+                body: body.to_string(),
+                user: None,
+                created_at: issue.created_at,
+                url: format!("https://linear.app/eng/issue/ENG-1#comment-{i}"),
+            })
+            .collect();
+        Candidate {
+            description: Some(searchable_text(description, MarkdownScope::default())),
+            comment_texts: comments
+                .iter()
+                .map(|c| searchable_text(&c.body, MarkdownScope::default()))
+                .collect(),
+            comments,
+            issue,
+            matched_issue: false,
+        }
+    }
+
+    fn options(input: &str, descriptions: bool, comments: bool) -> SearchOptions {
+        let query = Query::parse(input).unwrap();
+        let fetch_comments = query.uses_field(Field::Comment) || query.uses_field(Field::Author);
+        SearchOptions::new(input.to_string(), descriptions, comments)
+            .with_query(query)
+            .with_fetch_comments(fetch_comments)
+    }
+
+    fn find(candidate: Candidate, options: &SearchOptions) -> Option<Match> {
+        build_match(candidate, 0.0, options)
+    }
+
+    #[test]
+    fn test_qualifier_does_not_widen_unqualified_terms() {
+        // Only comments are searched, so "image" in the description is not
+        // a hit even though desc: looks there for "kafka"
+        let options = options("image desc:kafka", false, true);
+        assert!(find(candidate("Broker", "kafka image", &[]), &options).is_none());
+
+        let found = find(
+            candidate("Broker", "kafka image", &["image attached"]),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(found.desc_spans, vec![0..5]);
+        assert_eq!(found.comments_matched.len(), 1);
+    }
+
+    #[test]
+    fn test_comment_qualifier_does_not_search_comments_for_other_terms() {
+        let options = options("image comment:kafka", true, false);
+        assert!(find(candidate("Broker", "", &["kafka image"]), &options).is_none());
+
+        let found = find(candidate("Broker", "image", &["kafka image"]), &options).unwrap();
+        assert_eq!(found.comments_matched[0].spans, vec![0..5]);
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::Result;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::api::types::Team;
+use crate::api::LinearClient;
+use crate::text::typo_distance;
+
+/// How long cached teams are trusted before they are fetched again.
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Finds the team `input` refers to: its ID, its key (case-insensitive), its
+/// name (case-insensitive) or a prefix of exactly one team's name.
+pub fn resolve_team<'a>(input: &str, teams: &'a [Team]) -> Result<&'a Team> {
+    if let Some(team) = resolve_team_exactly(input, teams) {
+        return Ok(team);
+    }
+
+    let needle = input.trim().to_lowercase();
+    let prefixed: Vec<&Team> = teams
+        .iter()
+        .filter(|t| t.name.to_lowercase().starts_with(&needle))
+        .collect();
+
+    match prefixed.as_slice() {
+        [team] => Ok(team),
+        [] => {
+            let close = close_matches(&needle, teams);
+            if close.is_empty() {
+                anyhow::bail!("No team matches '{}'", input)
+            }
+            anyhow::bail!("No team matches '{}'. Did you mean: {}?", input, close)
+        }
+        ambiguous => anyhow::bail!(
+            "'{}' matches several teams: {}. Use the team key instead.",
+            input,
+            format_teams(ambiguous)
+        ),
+    }
+}
+
+/// Finds the team whose ID, key or name is `input`, ignoring case.
+fn resolve_team_exactly<'a>(input: &str, teams: &'a [Team]) -> Option<&'a Team> {
+    let needle = input.trim().to_lowercase();
+    teams
+        .iter()
+        .find(|t| t.id == input.trim())
+        .or_else(|| teams.iter().find(|t| t.key.to_lowercase() == needle))
+        .or_else(|| teams.iter().find(|t| t.name.to_lowercase() == needle))
+}
+
+/// Teams whose key or name is within a small edit distance of `needle`, or
+/// contains it.
+fn close_matches(needle: &str, teams: &[Team]) -> String {
+    let mut scored: Vec<(usize, &Team)> = teams
+        .iter()
+        .filter_map(|t| {
+            let key = t.key.to_lowercase();
+            let name = t.name.to_lowercase();
+            let distance = typo_distance(needle, &key).min(typo_distance(needle, &name));
+            let close = distance <= 2 || name.contains(needle) || key.contains(needle);
+            close.then_some((distance, t))
+        })
+        .collect();
+    scored.sort_by_key(|(distance, _)| *distance);
+
+    let teams: Vec<&Team> = scored.into_iter().take(5).map(|(_, t)| t).collect();
+    format_teams(&teams)
+}
+
+fn format_teams(teams: &[&Team]) -> String {
+    teams
+        .iter()
+        .map(|t| format!("{} ({})", t.key, t.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Whether `input` looks like a Linear team ID (a UUID) rather than a key or
+/// name, so it can be used without looking up the teams.
+fn is_team_id(input: &str) -> bool {
+    input.len() == 36 && input.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Teams of one workspace cached on disk, so team keys and names can be
+/// resolved without querying Linear on every run.
+pub struct TeamCache {
+    path: PathBuf,
+}
+
+impl TeamCache {
+    /// Cache for the workspace behind `api_key` at `endpoint`, stored in the
+    /// user's cache directory. Only a hash of the key is used.
+    pub fn for_workspace(endpoint: &str, api_key: &str) -> Option<Self> {
+        let hash = fnv1a([endpoint.as_bytes(), &[0], api_key.as_bytes()].concat());
+
+        let path = dirs::cache_dir()?
+            .join("linsearch")
+            .join(format!("teams-{:016x}.json", hash));
+        Some(Self { path })
+    }
+
+    /// The cached teams, unless there are none or they are older than
+    /// [`CACHE_TTL`].
+    pub fn load(&self) -> Option<Vec<Team>> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        if !modified.elapsed().is_ok_and(|age| age <= CACHE_TTL) {
+            return None;
+        }
+        let contents = fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn store(&self, teams: &[Team]) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string(teams)?)?;
+        Ok(())
+    }
+}
+
+/// 64-bit FNV-1a hash, which unlike the standard library's hashers stays the
+/// same across Rust releases, so cache file names do too.
+fn fnv1a(bytes: impl AsRef<[u8]>) -> u64 {
+    bytes
+        .as_ref()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+/// Fetches the workspace's teams from Linear and caches them.
+pub async fn fetch_and_cache_teams(
+    client: &LinearClient,
+    cache: Option<&TeamCache>,
+) -> Result<Vec<Team>> {
+    let teams = client.fetch_teams().await?;
+    if let Some(cache) = cache {
+        // A cache that cannot be written only costs a teams query next time
+        let _ = cache.store(&teams);
+    }
+    Ok(teams)
+}
+
+/// Team IDs resolved from team keys, names or IDs.
+pub struct ResolvedTeams {
+    pub ids: Vec<String>,
+    /// Whether cached teams were used, which may be out of date.
+    pub from_cache: bool,
+}
+
+/// Resolves team keys, names or IDs to team IDs. Cached teams are tried
+/// first, for exact IDs, keys and names only; the teams are fetched from
+/// Linear (and the cache refreshed) when an input cannot be resolved that
+/// way, so name prefixes are always matched against current teams.
+pub async fn resolve_team_ids(
+    client: &LinearClient,
+    inputs: &[String],
+    cache: Option<&TeamCache>,
+) -> Result<ResolvedTeams> {
+    if inputs.iter().all(|input| is_team_id(input)) {
+        return Ok(ResolvedTeams {
+            ids: inputs.to_vec(),
+            from_cache: false,
+        });
+    }
+
+    if let Some(teams) = cache.and_then(|c| c.load()) {
+        let resolved: Option<Vec<String>> = inputs
+            .iter()
+            .map(|input| resolve_team_exactly(input, &teams).map(|t| t.id.clone()))
+            .collect();
+        if let Some(ids) = resolved {
+            return Ok(ResolvedTeams {
+                ids,
+                from_cache: true,
+            });
+        }
+    }
+
+    Ok(ResolvedTeams {
+        ids: refresh_team_ids(client, inputs, cache).await?,
+        from_cache: false,
+    })
+}
+
+/// Resolves team keys, names or IDs against the teams as Linear has them
+/// now, refreshing the cache.
+pub async fn refresh_team_ids(
+    client: &LinearClient,
+    inputs: &[String],
+    cache: Option<&TeamCache>,
+) -> Result<Vec<String>> {
+    let teams = fetch_and_cache_teams(client, cache).await?;
+    inputs
+        .iter()
+        .map(|input| {
+            if is_team_id(input) && !teams.iter().any(|t| t.id == *input) {
+                return Ok(input.clone());
+            }
+            resolve_team(input, &teams).map(|t| t.id.clone())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn teams() -> Vec<Team> {
+        [
+            ("id-eng", "ENG", "Engineering"),
+            ("id-eng-ops", "OPS", "Engineering Ops"),
+            ("id-des", "DES", "Design"),
+            ("id-mkt", "MKT", "Marketing"),
+        ]
+        .into_iter()
+        .map(|(id, key, name)| Team {
+            id: id.to_string(),
+            key: key.to_string(),
+            name: name.to_string(),
+        })
+        .collect()
+    }
+
+    fn resolve(input: &str) -> Result<String> {
+        resolve_team(input, &teams()).map(|t| t.key.clone())
+    }
+
+    #[test]
+    fn test_resolve_exact_id_key_and_name() {
+        assert_eq!(resolve("id-des").unwrap(), "DES");
+        assert_eq!(resolve("eng").unwrap(), "ENG");
+        assert_eq!(resolve(" Design ").unwrap(), "DES");
+        // An exact name wins over it being a prefix of another name
+        assert_eq!(resolve("engineering").unwrap(), "ENG");
+    }
+
+    #[test]
+    fn test_resolve_unique_prefix() {
+        assert_eq!(resolve("mark").unwrap(), "MKT");
+        assert_eq!(resolve("Engineering O").unwrap(), "OPS");
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_prefix() {
+        let error = resolve("engin").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "'engin' matches several teams: ENG (Engineering), OPS (Engineering Ops). \
+             Use the team key instead."
+        );
+    }
+
+    #[test]
+    fn test_resolve_suggests_close_teams() {
+        let error = resolve("Desgin").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "No team matches 'Desgin'. Did you mean: DES (Design)?"
+        );
+        assert_eq!(
+            resolve("finance").unwrap_err().to_string(),
+            "No team matches 'finance'"
+        );
+    }
+
+    #[test]
+    fn test_close_matches_orders_by_distance() {
+        assert_eq!(close_matches("mkr", &teams()), "MKT (Marketing)");
+        // The key is an exact hit, `des` is two edits away
+        assert_eq!(
+            close_matches("ops", &teams()),
+            "OPS (Engineering Ops), DES (Design)"
+        );
+        assert_eq!(
+            close_matches("eg", &teams()),
+            "ENG (Engineering), DES (Design)"
+        );
+        assert_eq!(close_matches("finance", &teams()), "");
+    }
+
+    #[test]
+    fn test_cache_hash_is_stable() {
+        assert_eq!(fnv1a(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a("foobar"), 0x8594_4171_f739_67e8);
+    }
+}
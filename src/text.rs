@@ -0,0 +1,375 @@
+use std::ops::Range;
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+/// Edit distance that also counts swapping two adjacent characters as one
+/// edit, so `recieve` is one typo away from `receive`.
+pub fn typo_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+
+    rows[a.len()][b.len()]
+}
+
+/// Text transformed for matching, remembering where each byte of the
+/// transformed text came from in the original.
+#[derive(Debug, Clone, Default)]
+pub struct MappedText {
+    pub text: String,
+    /// Original byte range of the character each byte of `text` came from,
+    /// as start and end offsets.
+    starts: Vec<usize>,
+    ends: Vec<usize>,
+    /// Original offset that the end of `text` corresponds to.
+    end: usize,
+}
+
+impl MappedText {
+    /// Prepares `original` for matching: lowercases it unless
+    /// `case_sensitive`, and with `fold_accents` decomposes each character
+    /// and drops combining marks, so `é` becomes `e`.
+    pub fn normalize(original: &str, case_sensitive: bool, fold_accents: bool) -> Self {
+        let mut mapped = Self {
+            text: String::with_capacity(original.len()),
+            starts: Vec::with_capacity(original.len()),
+            ends: Vec::with_capacity(original.len()),
+            end: original.len(),
+        };
+
+        let push = |text: &mut String, c: char| {
+            if case_sensitive {
+                text.push(c);
+            } else {
+                text.extend(c.to_lowercase());
+            }
+        };
+
+        for (offset, c) in original.char_indices() {
+            if fold_accents {
+                decompose_compatible(c, |d| {
+                    if !is_combining_mark(d) {
+                        push(&mut mapped.text, d);
+                    }
+                });
+            } else {
+                push(&mut mapped.text, c);
+            }
+            mapped.starts.resize(mapped.text.len(), offset);
+            mapped.ends.resize(mapped.text.len(), offset + c.len_utf8());
+        }
+
+        mapped
+    }
+
+    /// Appends `range` of `original` unchanged.
+    pub fn push_original(&mut self, original: &str, range: Range<usize>) {
+        for (i, c) in original[range.clone()].char_indices() {
+            self.text.push(c);
+            self.starts.resize(self.text.len(), range.start + i);
+            self.ends
+                .resize(self.text.len(), range.start + i + c.len_utf8());
+        }
+        self.end = range.end;
+    }
+
+    /// Appends a line break that stands for nothing in the original, found
+    /// at offset `at`, so matches can't run across it.
+    pub fn push_break(&mut self, at: usize) {
+        self.text.push('\n');
+        self.starts.push(at);
+        self.ends.push(at);
+        self.end = at;
+    }
+
+    /// Maps a byte range of the transformed text back to the original text.
+    pub fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let start = self.starts.get(range.start).copied().unwrap_or(self.end);
+        // The end maps to the end of the last character the range covers
+        let end = if range.end == range.start {
+            start
+        } else {
+            self.ends[range.end - 1]
+        };
+        start..end
+    }
+}
+
+/// Most snippets taken from a single text.
+pub const MAX_SNIPPETS: usize = 3;
+
+/// An excerpt around one or more matches, with whitespace collapsed so it
+/// fits on one line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    /// Byte ranges of the matches in `text`.
+    pub highlights: Vec<Range<usize>>,
+    /// Whether text was cut before the excerpt.
+    pub truncated_start: bool,
+    /// Whether text was cut after the excerpt.
+    pub truncated_end: bool,
+}
+
+/// Excerpts of `text` showing each of the sorted, non-overlapping `spans`
+/// with up to `context` characters on either side. Excerpts that would
+/// overlap are joined.
+pub fn snippets(text: &str, spans: &[Range<usize>], context: usize) -> Vec<Snippet> {
+    let mut windows: Vec<(Range<usize>, Vec<Range<usize>>)> = Vec::new();
+    for span in spans {
+        let start = text[..span.start]
+            .char_indices()
+            .rev()
+            .take(context)
+            .last()
+            .map_or(span.start, |(i, _)| i);
+        let end = text[span.end..]
+            .char_indices()
+            .nth(context)
+            .map_or(text.len(), |(i, _)| span.end + i);
+
+        match windows.last_mut() {
+            Some((window, highlights)) if start <= window.end => {
+                window.end = window.end.max(end);
+                highlights.push(span.clone());
+            }
+            _ => {
+                if windows.len() == MAX_SNIPPETS {
+                    break;
+                }
+                windows.push((start..end, vec![span.clone()]));
+            }
+        }
+    }
+
+    windows
+        .into_iter()
+        .map(|(window, spans)| {
+            let mut snippet = Snippet {
+                text: String::new(),
+                highlights: Vec::new(),
+                truncated_start: window.start > 0,
+                truncated_end: window.end < text.len(),
+            };
+            let mut pos = window.start;
+            for span in spans {
+                push_collapsed(&mut snippet.text, &text[pos..span.start]);
+                let start = snippet.text.len();
+                push_collapsed(&mut snippet.text, &text[span.clone()]);
+                snippet.highlights.push(start..snippet.text.len());
+                pos = span.end;
+            }
+            push_collapsed(&mut snippet.text, &text[pos..window.end]);
+            trim_snippet(&mut snippet);
+            snippet
+        })
+        .collect()
+}
+
+/// Drops a space left at either end of the snippet by collapsing.
+fn trim_snippet(snippet: &mut Snippet) {
+    let len = snippet.text.len();
+    if snippet.text.ends_with(' ') && snippet.highlights.last().is_none_or(|h| h.end != len) {
+        snippet.text.pop();
+    }
+    if snippet.text.starts_with(' ') && snippet.highlights.first().is_none_or(|h| h.start != 0) {
+        snippet.text.remove(0);
+        for highlight in &mut snippet.highlights {
+            *highlight = highlight.start - 1..highlight.end - 1;
+        }
+    }
+}
+
+/// Appends `text` with each run of whitespace replaced by a single space.
+fn push_collapsed(out: &mut String, text: &str) {
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !out.ends_with(' ') {
+                out.push(' ');
+            }
+        } else {
+            out.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_lowercases() {
+        let mapped = MappedText::normalize("Kafka LAG", false, false);
+        assert_eq!(mapped.text, "kafka lag");
+        assert_eq!(mapped.original_range(6..9), 6..9);
+
+        let mapped = MappedText::normalize("Kafka LAG", true, false);
+        assert_eq!(mapped.text, "Kafka LAG");
+    }
+
+    #[test]
+    fn test_folded_accent_maps_to_whole_character() {
+        let original = "Café au lait";
+        let mapped = MappedText::normalize(original, false, true);
+        assert_eq!(mapped.text, "cafe au lait");
+        // `é` is two bytes in the original but `e` is one
+        assert_eq!(mapped.original_range(0..4), 0..5);
+        assert_eq!(&original[mapped.original_range(3..4)], "é");
+        assert_eq!(&original[mapped.original_range(5..7)], "au");
+    }
+
+    #[test]
+    fn test_unfolded_accent_keeps_bytes() {
+        let mapped = MappedText::normalize("CAFÉ", false, false);
+        assert_eq!(mapped.text, "café");
+        assert_eq!(mapped.original_range(3..5), 3..5);
+    }
+
+    #[test]
+    fn test_ligature_expands() {
+        let original = "ﬁle";
+        let mapped = MappedText::normalize(original, false, true);
+        assert_eq!(mapped.text, "file");
+        // Both letters of the ligature map to all of it
+        assert_eq!(mapped.original_range(0..1), 0..3);
+        assert_eq!(mapped.original_range(1..2), 0..3);
+        assert_eq!(&original[mapped.original_range(1..4)], "ﬁle");
+    }
+
+    #[test]
+    fn test_lowercase_that_grows() {
+        // `İ` lowercases to `i` and a combining dot, three bytes from two
+        let original = "İstanbul";
+        let mapped = MappedText::normalize(original, false, false);
+        assert_eq!(mapped.text, "i\u{307}stanbul");
+        assert_eq!(mapped.original_range(0..3), 0..2);
+        assert_eq!(&original[mapped.original_range(3..10)], "stanbul");
+
+        let folded = MappedText::normalize(original, false, true);
+        assert_eq!(folded.text, "istanbul");
+        assert_eq!(folded.original_range(0..8), 0..original.len());
+    }
+
+    #[test]
+    fn test_empty_range_at_end() {
+        let mapped = MappedText::normalize("héllo", false, true);
+        let end = mapped.text.len();
+        assert_eq!(mapped.original_range(end..end), 6..6);
+    }
+
+    #[test]
+    fn test_pushed_pieces_and_breaks() {
+        let original = "**ab** cd";
+        let mut mapped = MappedText::default();
+        mapped.push_original(original, 2..4);
+        mapped.push_break(6);
+        mapped.push_original(original, 7..9);
+        assert_eq!(mapped.text, "ab\ncd");
+        assert_eq!(mapped.original_range(0..2), 2..4);
+        assert_eq!(mapped.original_range(3..5), 7..9);
+        assert_eq!(mapped.original_range(0..5), 2..9);
+    }
+
+    fn snippet_at(text: &str, needle: &str, context: usize) -> Snippet {
+        let start = text.find(needle).unwrap();
+        let span = start..start + needle.len();
+        let mut found = snippets(text, std::slice::from_ref(&span), context);
+        assert_eq!(found.len(), 1);
+        found.remove(0)
+    }
+
+    fn highlighted(snippet: &Snippet) -> Vec<&str> {
+        snippet
+            .highlights
+            .iter()
+            .map(|h| &snippet.text[h.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn test_snippet_context() {
+        let snippet = snippet_at("aaaa match bbbb", "match", 2);
+        assert_eq!(snippet.text, "a match b");
+        assert_eq!(highlighted(&snippet), ["match"]);
+        assert!(snippet.truncated_start && snippet.truncated_end);
+
+        let snippet = snippet_at("aaaa match bbbb", "match", 60);
+        assert_eq!(snippet.text, "aaaa match bbbb");
+        assert!(!snippet.truncated_start && !snippet.truncated_end);
+    }
+
+    #[test]
+    fn test_snippet_context_counts_characters() {
+        let snippet = snippet_at("ééé match ééé", "match", 2);
+        assert_eq!(snippet.text, "é match é");
+        assert_eq!(highlighted(&snippet), ["match"]);
+    }
+
+    #[test]
+    fn test_close_matches_share_a_snippet() {
+        let text = "one kafka two lag three";
+        let found = snippets(text, &[4..9, 14..17], 6);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].text, "one kafka two lag three");
+        assert_eq!(highlighted(&found[0]), ["kafka", "lag"]);
+    }
+
+    #[test]
+    fn test_distant_matches_get_own_snippets() {
+        let text = "kafka and then a long way later lag";
+        let found = snippets(text, &[0..5, 32..35], 3);
+        let texts: Vec<&str> = found.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, ["kafka an", "er lag"]);
+        assert!(!found[0].truncated_start && found[0].truncated_end);
+        assert!(found[1].truncated_start && !found[1].truncated_end);
+    }
+
+    #[test]
+    fn test_snippets_are_capped() {
+        let text = "x ".repeat(50);
+        let spans: Vec<Range<usize>> = (0..5).map(|i| i * 20..i * 20 + 1).collect();
+        let found = snippets(&text, &spans, 2);
+        assert_eq!(found.len(), MAX_SNIPPETS);
+        assert!(snippets(&text, &[], 2).is_empty());
+    }
+
+    #[test]
+    fn test_whitespace_is_collapsed() {
+        let snippet = snippet_at("foo\n\n  kafka\t\tlag   bar", "kafka\t\tlag", 60);
+        assert_eq!(snippet.text, "foo kafka lag bar");
+        assert_eq!(highlighted(&snippet), ["kafka lag"]);
+    }
+
+    #[test]
+    fn test_trimmed_space_shifts_highlights() {
+        let snippet = snippet_at("x\n match\n y", "match", 1);
+        assert_eq!(snippet.text, "match");
+        assert_eq!(highlighted(&snippet), ["match"]);
+        assert!(snippet.truncated_start && snippet.truncated_end);
+    }
+
+    #[test]
+    fn test_highlighted_spaces_are_kept() {
+        let snippet = snippet_at("a  b", "  ", 0);
+        assert_eq!(snippet.text, " ");
+        assert_eq!(highlighted(&snippet), [" "]);
+    }
+}
@@ -1,23 +1,29 @@
+use chrono::{DateTime, Local, Utc};
 use colored::Colorize;
-use inquire::{Confirm, Select};
+use inquire::{Confirm, MultiSelect};
 use std::fs::File;
 use std::io::Write;
+use std::time::Duration;
 
-use crate::api::types::{Match, Team};
+use crate::api::rate_limit::RateLimitStatus;
+use crate::api::types::{IssueRef, Match, Team};
+use crate::api::LinearError;
+use crate::text::Snippet;
 
-pub fn select_team_interactive(teams: &[Team]) -> anyhow::Result<String> {
+pub fn select_teams_interactive(teams: &[Team]) -> anyhow::Result<Vec<String>> {
     let options: Vec<String> = teams
         .iter()
         .map(|t| format!("[{}] {}", t.key.bright_cyan(), t.name))
         .collect();
 
-    let selection = Select::new("👥 Select a team:", options.clone())
+    let selection = MultiSelect::new("👥 Select teams (space to toggle):", options)
         .with_page_size(10)
-        .prompt()?;
+        .raw_prompt()?;
 
-    // Find the selected team by matching the formatted string
-    let selected_index = options.iter().position(|o| o == &selection).unwrap();
-    Ok(teams[selected_index].id.clone())
+    Ok(selection
+        .into_iter()
+        .map(|option| teams[option.index].id.clone())
+        .collect())
 }
 
 pub fn confirm_search_descriptions() -> anyhow::Result<bool> {
@@ -34,9 +40,41 @@ pub fn confirm_search_comments() -> anyhow::Result<bool> {
         .map_err(|e| anyhow::anyhow!("Prompt error: {}", e))
 }
 
+fn format_max_requests(max_requests: Option<usize>) -> String {
+    max_requests
+        .map(|max| max.to_string())
+        .unwrap_or_else(|| "unlimited".to_string())
+}
+
+fn format_rate_limit(rate_limit: Option<RateLimitStatus>) -> String {
+    let Some(rl) = rate_limit else {
+        return "not reported yet".to_string();
+    };
+
+    let quota = |remaining: Option<u64>, limit: Option<u64>, unit: &str| match (remaining, limit) {
+        (Some(remaining), Some(limit)) => Some(format!("{}/{} {}", remaining, limit, unit)),
+        (Some(remaining), None) => Some(format!("{} {}", remaining, unit)),
+        _ => None,
+    };
+
+    let parts: Vec<String> = [
+        quota(rl.requests_remaining, rl.requests_limit, "requests"),
+        quota(rl.complexity_remaining, rl.complexity_limit, "complexity"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    if parts.is_empty() {
+        "not reported yet".to_string()
+    } else {
+        format!("{} remaining", parts.join(", "))
+    }
+}
+
 pub fn display_search_info(
     search_term: &str,
-    max_requests: usize,
+    rate_limit: Option<RateLimitStatus>,
     descriptions: bool,
     comments: bool,
 ) {
@@ -48,8 +86,8 @@ pub fn display_search_info(
     );
     println!(
         "{} {}",
-        "⚡ Max requests:".bright_blue(),
-        max_requests.to_string().bright_white()
+        "⚡ Linear quota:".bright_blue(),
+        format_rate_limit(rate_limit).bright_white()
     );
 
     let mut search_in = Vec::new();
@@ -72,18 +110,48 @@ pub fn display_search_info(
     println!();
 }
 
+/// `'term'`, or `any of 'a', 'b'` for several terms.
+fn describe_terms(search_terms: &[String]) -> String {
+    let quoted: Vec<String> = search_terms.iter().map(|t| format!("'{}'", t)).collect();
+    match quoted.as_slice() {
+        [single] => single.clone(),
+        _ => format!("any of {}", quoted.join(", ")),
+    }
+}
+
+/// Number of matches for each search term, in the order given.
+fn term_totals<'a>(matches: &[Match], search_terms: &'a [String]) -> Vec<(&'a str, usize)> {
+    search_terms
+        .iter()
+        .map(|term| {
+            let count = matches
+                .iter()
+                .filter(|m| m.matched_terms.contains(term))
+                .count();
+            (term.as_str(), count)
+        })
+        .collect()
+}
+
 pub fn display_results(
     matches: &[Match],
-    search_term: &str,
+    search_terms: &[String],
     request_count: usize,
-    max_requests: usize,
+    max_requests: Option<usize>,
+    rate_limit: Option<RateLimitStatus>,
+    rollup: bool,
 ) {
     println!();
     println!(
         "{} {}/{}",
         "📊 Total API requests used:".bright_blue(),
         request_count.to_string().bright_white(),
-        max_requests.to_string().bright_white()
+        format_max_requests(max_requests).bright_white()
+    );
+    println!(
+        "{} {}",
+        "⚡ Linear quota:".bright_blue(),
+        format_rate_limit(rate_limit).bright_white()
     );
     println!();
 
@@ -91,7 +159,7 @@ pub fn display_results(
         println!(
             "{} {}",
             "❌ No issues found containing".yellow(),
-            format!("'{}'", search_term).bright_yellow()
+            describe_terms(search_terms).bright_yellow()
         );
         return;
     }
@@ -100,66 +168,385 @@ pub fn display_results(
         "{} {} {}",
         "✨ Found".green().bold(),
         matches.len().to_string().bright_green().bold(),
-        format!("issue(s) containing '{}':", search_term)
+        format!("issue(s) containing {}:", describe_terms(search_terms))
             .green()
             .bold()
     );
     println!();
 
-    for m in matches {
-        // Issue header - show team and title, hide ID
-        println!(
-            "{}  {}",
-            m.team.bright_cyan(),
-            m.title.bright_white().bold()
-        );
-        println!("🔗 {}", m.url.bright_blue().underline());
-
-        // Match locations
-        let mut flags = Vec::new();
-        if m.in_title {
-            flags.push("title".green());
+    if search_terms.len() > 1 {
+        println!("{}", "📋 Issues per term:".bright_blue());
+        for (term, count) in term_totals(matches, search_terms) {
+            println!(
+                "   {} {}",
+                format!("{}:", term).bright_white(),
+                count.to_string().bright_green()
+            );
         }
-        if m.in_desc {
-            flags.push("description".green());
+        println!();
+    }
+
+    if rollup {
+        for family in group_by_parent(matches) {
+            if !family.sub_issues.is_empty() {
+                display_family_header(&family);
+            }
+            for m in family.head.iter().chain(&family.sub_issues) {
+                display_match(m);
+            }
         }
-        if !flags.is_empty() {
+        return;
+    }
+
+    let groups = group_by_team(matches);
+    let grouped = groups.len() > 1;
+    for (team, team_matches) in groups {
+        if grouped {
             println!(
-                "   {} {}",
-                "✅".green(),
-                format!(
-                    "matched in: {}",
-                    flags
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
-                .bright_white()
+                "{} {}",
+                format!("👥 {}", team).bright_cyan().bold(),
+                format!("({} issue(s))", team_matches.len()).bright_black()
             );
+            println!();
+        }
+        for m in team_matches {
+            display_match(m);
         }
+    }
+}
+
+fn display_family_header(family: &Family) {
+    println!(
+        "{} {}  {}  {}",
+        "🌳".bright_white(),
+        family.id.bright_magenta().bold(),
+        family.title.bright_white().bold(),
+        format!("({} matching sub-issue(s))", family.sub_issues.len()).bright_black()
+    );
+    if family.head.is_none() {
+        println!("🔗 {}", family.url.bright_blue().underline());
+    }
+    println!();
+}
+
+fn display_match(m: &Match) {
+    // Issue header - show team and title, hide ID
+    let archive_tag = archive_status(m)
+        .map(|status| format!("  🗄️ {}", status).bright_red().to_string())
+        .unwrap_or_default();
+    println!(
+        "{}  {}{}",
+        m.team.bright_cyan(),
+        m.title.bright_white().bold(),
+        archive_tag
+    );
+    println!("🔗 {}", m.url.bright_blue().underline());
+    println!(
+        "   {} {}",
+        "⭐ score:".bright_white(),
+        format!("{:.2}", m.score).bright_yellow()
+    );
+    let details: Vec<String> = issue_details(m)
+        .into_iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect();
+    println!(
+        "   {} {}",
+        "📋".bright_white(),
+        details.join(" · ").bright_white()
+    );
+    let dates: Vec<String> = issue_dates(m)
+        .into_iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect();
+    println!(
+        "   {} {}",
+        "🕒".bright_white(),
+        dates.join(" · ").bright_black()
+    );
+
+    // Match locations
+    let mut flags = Vec::new();
+    if m.in_title {
+        flags.push("title".green());
+    }
+    if m.in_desc {
+        flags.push("description".green());
+    }
+    if !flags.is_empty() {
+        println!(
+            "   {} {}",
+            "✅".green(),
+            format!(
+                "matched in: {}",
+                flags
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+            .bright_white()
+        );
+    }
+    if !m.matched_terms.is_empty() {
+        println!(
+            "   {} {}",
+            "🏷️  terms:".bright_white(),
+            m.matched_terms.join(", ").bright_green()
+        );
+    }
+    if !m.matched_words.is_empty() {
+        println!(
+            "   {} {} {}",
+            "🔤".bright_white(),
+            "matched words:".bright_white(),
+            m.matched_words.join(", ").bright_yellow()
+        );
+    }
+    for snippet in &m.desc_snippets {
+        println!("   {} {}", "📝".bright_white(), highlight_terminal(snippet));
+    }
 
-        // Comment matches
-        if !m.comments_matched.is_empty() {
+    // Comment matches
+    if !m.comments_matched.is_empty() {
+        println!(
+            "   {} {} {}",
+            "💬".bright_white(),
+            m.comments_matched.len().to_string().bright_yellow(),
+            "comment(s) matched:".bright_white()
+        );
+        for c in &m.comments_matched {
             println!(
-                "   {} {} {}",
-                "💬".bright_white(),
-                m.comments_matched.len().to_string().bright_yellow(),
-                "comment(s) matched:".bright_white()
+                "     {} {} {} {} {}",
+                "•".bright_black(),
+                c.commenter.bright_cyan(),
+                "•".bright_black(),
+                c.created_at
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string()
+                    .bright_black(),
+                format!("🔗 {}", c.url).bright_blue()
             );
-            for c in &m.comments_matched {
-                println!(
-                    "     {} {} {} {} {}",
-                    "•".bright_black(),
-                    c.commenter.bright_cyan(),
-                    "•".bright_black(),
-                    c.created_at.bright_black(),
-                    format!("🔗 {}", c.url).bright_blue()
-                );
+            for snippet in &c.snippets {
+                println!("       {}", highlight_terminal(snippet));
             }
         }
-        println!();
     }
+    println!();
+}
+
+/// Workflow and ownership details worth showing for an issue, as
+/// name/value pairs.
+fn issue_details(m: &Match) -> Vec<(&'static str, String)> {
+    let details = &m.details;
+    let mut fields = Vec::new();
+    if let Some(state) = &details.state {
+        fields.push(("State", format!("{} ({})", state.name, state.state_type)));
+    }
+    let assignee = details.assignee.as_ref().map(|u| u.name.clone());
+    fields.push((
+        "Assignee",
+        assignee.unwrap_or_else(|| "Unassigned".to_string()),
+    ));
+    if let Some(creator) = &details.creator {
+        fields.push(("Creator", creator.name.clone()));
+    }
+    if details.priority > 0.0 {
+        fields.push(("Priority", details.priority_label.clone()));
+    }
+    if !details.labels.nodes.is_empty() {
+        let labels: Vec<&str> = details
+            .labels
+            .nodes
+            .iter()
+            .map(|l| l.name.as_str())
+            .collect();
+        fields.push(("Labels", labels.join(", ")));
+    }
+    if let Some(estimate) = details.estimate {
+        fields.push(("Estimate", estimate.to_string()));
+    }
+    if let Some(project) = &details.project {
+        fields.push(("Project", project.name.clone()));
+    }
+    if let Some(cycle) = &details.cycle {
+        let cycle = match &cycle.name {
+            Some(name) => format!("{} (#{})", name, cycle.number),
+            None => format!("#{}", cycle.number),
+        };
+        fields.push(("Cycle", cycle));
+    }
+    if let Some(parent) = &m.parent {
+        fields.push(("Parent", format!("{} {}", parent.identifier, parent.title)));
+    }
+    if !m.children.is_empty() {
+        fields.push(("Sub-issues", m.children.join(", ")));
+    }
+    fields
+}
+
+/// When an issue was created, last updated, and completed or canceled.
+fn issue_dates(m: &Match) -> Vec<(&'static str, String)> {
+    let date = |at: &DateTime<Utc>| at.format("%Y-%m-%d").to_string();
+    let mut fields = vec![
+        ("Created", date(&m.created_at)),
+        ("Updated", date(&m.updated_at)),
+    ];
+    if let Some(completed_at) = &m.details.completed_at {
+        fields.push(("Completed", date(completed_at)));
+    }
+    if let Some(canceled_at) = &m.details.canceled_at {
+        fields.push(("Canceled", date(canceled_at)));
+    }
+    if let Some(archived_at) = &m.details.archived_at {
+        fields.push(("Archived", date(archived_at)));
+    }
+    fields
+}
+
+/// "trashed" or "archived" for an issue Linear hides by default.
+fn archive_status(m: &Match) -> Option<&'static str> {
+    if m.details.is_trashed() {
+        Some("trashed")
+    } else if m.details.is_archived() {
+        Some("archived")
+    } else {
+        None
+    }
+}
+
+/// Renders a snippet with its matches in bold, marking cut-off text with
+/// an ellipsis. `plain` and `highlight` format the text between and of the
+/// matches.
+fn render_snippet(
+    snippet: &Snippet,
+    plain: impl Fn(&str) -> String,
+    highlight: impl Fn(&str) -> String,
+) -> String {
+    let mut out = String::new();
+    if snippet.truncated_start {
+        out.push('…');
+    }
+    let mut pos = 0;
+    for range in &snippet.highlights {
+        out.push_str(&plain(&snippet.text[pos..range.start]));
+        out.push_str(&highlight(&snippet.text[range.clone()]));
+        pos = range.end;
+    }
+    out.push_str(&plain(&snippet.text[pos..]));
+    if snippet.truncated_end {
+        out.push('…');
+    }
+    out
+}
+
+fn highlight_terminal(snippet: &Snippet) -> String {
+    render_snippet(
+        snippet,
+        |text| text.bright_black().to_string(),
+        |text| text.bright_yellow().bold().to_string(),
+    )
+}
+
+fn highlight_markdown(snippet: &Snippet) -> String {
+    render_snippet(snippet, escape_markdown, |text| {
+        format!("**{}**", escape_markdown(text))
+    })
+}
+
+/// Escapes characters that would otherwise be read as Markdown formatting.
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '|' | '~'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Groups matches by team, keeping teams in order of first appearance and
+/// matches in their original order within each team.
+fn group_by_team(matches: &[Match]) -> Vec<(&str, Vec<&Match>)> {
+    let mut groups: Vec<(&str, Vec<&Match>)> = Vec::new();
+    for m in matches {
+        match groups.iter_mut().find(|(team, _)| *team == m.team) {
+            Some((_, team_matches)) => team_matches.push(m),
+            None => groups.push((&m.team, vec![m])),
+        }
+    }
+    groups
+}
+
+/// A top-level issue with its matching sub-issues, as listed by `--rollup`.
+struct Family<'a> {
+    id: &'a str,
+    title: &'a str,
+    url: &'a str,
+    /// The top-level issue, if it matched itself.
+    head: Option<&'a Match>,
+    sub_issues: Vec<&'a Match>,
+}
+
+/// Groups matches under their topmost ancestor among the matches, or under
+/// that ancestor's parent if it has one, in the order of `matches`.
+fn group_by_parent(matches: &[Match]) -> Vec<Family<'_>> {
+    let find = |id: &str| matches.iter().find(|m| m.id == id);
+    let mut families: Vec<Family> = Vec::new();
+    for m in matches {
+        // Climb through the ancestors that matched too
+        let mut top = m;
+        let mut parent: Option<&IssueRef> = None;
+        while let Some(top_parent) = &top.parent {
+            match find(&top_parent.identifier) {
+                Some(parent_match) => top = parent_match,
+                None => {
+                    parent = Some(top_parent);
+                    break;
+                }
+            }
+        }
+
+        let id = parent.map_or(top.id.as_str(), |p| p.identifier.as_str());
+        let index = match families.iter().position(|f| f.id == id) {
+            Some(index) => index,
+            None => {
+                families.push(match parent {
+                    Some(parent) => Family {
+                        id,
+                        title: &parent.title,
+                        url: &parent.url,
+                        head: None,
+                        sub_issues: Vec::new(),
+                    },
+                    None => Family {
+                        id,
+                        title: &top.title,
+                        url: &top.url,
+                        head: Some(top),
+                        sub_issues: Vec::new(),
+                    },
+                });
+                families.len() - 1
+            }
+        };
+        if families[index].head.is_none_or(|head| head.id != m.id) {
+            families[index].sub_issues.push(m);
+        }
+    }
+    families
+}
+
+pub fn display_rate_limit_wait(wait: Duration) {
+    eprintln!(
+        "{} {}",
+        "⏳ Linear rate limit exhausted,".yellow(),
+        format!("waiting {}s for reset", wait.as_secs()).bright_yellow()
+    );
 }
 
 pub fn display_rate_limit_warning(max_requests: usize) {
@@ -173,6 +560,56 @@ pub fn display_rate_limit_warning(max_requests: usize) {
     println!();
 }
 
+pub fn display_error(err: &anyhow::Error) {
+    let message = match err.downcast_ref::<LinearError>() {
+        Some(LinearError::Authentication(detail)) => format!(
+            "Authentication failed ({}). Check LINEAR_API_KEY or --api-key.",
+            detail
+        ),
+        Some(LinearError::RateLimited { reset_at }) => match reset_at
+            .and_then(|ms| i64::try_from(ms).ok())
+            .and_then(DateTime::from_timestamp_millis)
+        {
+            Some(reset_at) => format!(
+                "Linear rate limit exceeded. The quota resets at {}; try again later or raise --max-retries.",
+                reset_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            ),
+            None => "Linear rate limit exceeded. Try again later or raise --max-retries."
+                .to_string(),
+        },
+        Some(LinearError::NotFound(detail)) => format!(
+            "Not found: {}. Check the team or issue ID and that your API key can access it.",
+            detail
+        ),
+        Some(LinearError::ComplexityExceeded(detail)) => format!(
+            "Query too complex for Linear ({}). Try a lower --inline-comments value.",
+            detail
+        ),
+        Some(LinearError::Network(e)) => format!(
+            "Could not reach the Linear API: {}. Check your connection and --endpoint.",
+            e
+        ),
+        Some(LinearError::Http(status)) => format!("The Linear API returned {}.", status),
+        Some(LinearError::GraphQL(errors)) => {
+            let details: Vec<String> = errors
+                .iter()
+                .map(|e| match e.code() {
+                    Some(code) => format!("{} [{}]", e.message, code),
+                    None => e.message.clone(),
+                })
+                .collect();
+            format!("Linear returned errors: {}", details.join("; "))
+        }
+        Some(LinearError::BudgetExhausted { max_requests }) => format!(
+            "Request budget of {} requests exhausted. Raise it with --max-requests.",
+            max_requests
+        ),
+        None => format!("{:#}", err),
+    };
+
+    eprintln!("{} {}", "❌ Error:".red().bold(), message);
+}
+
 pub fn display_fetching_teams() {
     println!(
         "{}",
@@ -180,6 +617,20 @@ pub fn display_fetching_teams() {
     );
 }
 
+/// Announces the issue `--within` searches under; `subtree` starts with it.
+pub fn display_subtree(subtree: &[IssueRef]) {
+    let Some(root) = subtree.first() else {
+        return;
+    };
+    println!(
+        "{} {} {} {}",
+        "🌳 Searching within:".bright_blue(),
+        root.identifier.bright_magenta().bold(),
+        root.title.bright_white(),
+        format!("and {} sub-issue(s)", subtree.len() - 1).bright_black()
+    );
+}
+
 pub fn display_fetching_issues() {
     println!("{}", "🔍 Searching issues...".bright_blue().bold());
 }
@@ -187,67 +638,162 @@ pub fn display_fetching_issues() {
 pub fn save_results_to_file(
     file_path: &str,
     matches: &[Match],
-    search_term: &str,
+    search_terms: &[String],
     request_count: usize,
-    max_requests: usize,
+    max_requests: Option<usize>,
+    rate_limit: Option<RateLimitStatus>,
+    rollup: bool,
 ) -> anyhow::Result<()> {
     let mut file = File::create(file_path)?;
 
     // Write header
     writeln!(file, "# LinSearch Results\n")?;
-    writeln!(file, "**Search term:** `{}`", search_term)?;
+    if let [search_term] = search_terms {
+        writeln!(file, "**Search term:** `{}`", search_term)?;
+    } else {
+        let quoted: Vec<String> = search_terms.iter().map(|t| format!("`{}`", t)).collect();
+        writeln!(file, "**Search terms:** {}", quoted.join(", "))?;
+    }
     writeln!(file, "**Total results:** {}", matches.len())?;
     writeln!(
         file,
-        "**API requests used:** {}/{}\n",
-        request_count, max_requests
+        "**API requests used:** {}/{}",
+        request_count,
+        format_max_requests(max_requests)
+    )?;
+    writeln!(
+        file,
+        "**Linear quota:** {}\n",
+        format_rate_limit(rate_limit)
     )?;
     writeln!(file, "---\n")?;
 
     if matches.is_empty() {
-        writeln!(file, "No issues found containing '{}'.", search_term)?;
+        writeln!(
+            file,
+            "No issues found containing {}.",
+            describe_terms(search_terms)
+        )?;
         return Ok(());
     }
 
-    // Write each match
-    for (idx, m) in matches.iter().enumerate() {
-        writeln!(file, "## {}. {}", idx + 1, m.title)?;
-        writeln!(file)?;
-        writeln!(file, "**Team:** {}", m.team)?;
-        writeln!(file, "**URL:** {}", m.url)?;
-        writeln!(file)?;
+    if search_terms.len() > 1 {
+        writeln!(file, "| Term | Issues |")?;
+        writeln!(file, "| --- | ---: |")?;
+        for (term, count) in term_totals(matches, search_terms) {
+            writeln!(file, "| `{}` | {} |", term, count)?;
+        }
+        writeln!(file, "\n---\n")?;
+    }
 
-        // Match locations
-        let mut flags = Vec::new();
-        if m.in_title {
-            flags.push("title");
+    if rollup {
+        // Matching sub-issues go under a heading for their parent
+        let mut number = 0;
+        for family in group_by_parent(matches) {
+            let heading = if family.sub_issues.is_empty() {
+                "##"
+            } else {
+                writeln!(
+                    file,
+                    "## {}: {} ({} matching sub-issue(s))\n",
+                    family.id,
+                    family.title,
+                    family.sub_issues.len()
+                )?;
+                if family.head.is_none() {
+                    writeln!(file, "**URL:** {}\n", family.url)?;
+                }
+                "###"
+            };
+            for m in family.head.iter().chain(&family.sub_issues) {
+                number += 1;
+                write_match(&mut file, number, heading, m)?;
+            }
         }
-        if m.in_desc {
-            flags.push("description");
+        return Ok(());
+    }
+
+    // Write each match, under a heading per team when there are several
+    let groups = group_by_team(matches);
+    let grouped = groups.len() > 1;
+    let mut number = 0;
+    for (team, team_matches) in groups {
+        if grouped {
+            writeln!(file, "## {} ({} issue(s))\n", team, team_matches.len())?;
         }
-        if !flags.is_empty() {
-            writeln!(file, "**Matched in:** {}", flags.join(", "))?;
+        for m in team_matches {
+            number += 1;
+            write_match(&mut file, number, if grouped { "###" } else { "##" }, m)?;
         }
+    }
+
+    Ok(())
+}
+
+fn write_match(file: &mut File, number: usize, heading: &str, m: &Match) -> anyhow::Result<()> {
+    let archive_tag = archive_status(m)
+        .map(|status| format!(" *({})*", status))
+        .unwrap_or_default();
+    writeln!(file, "{} {}. {}{}", heading, number, m.title, archive_tag)?;
+    writeln!(file)?;
+    writeln!(file, "**Team:** {}", m.team)?;
+    writeln!(file, "**URL:** {}", m.url)?;
+    writeln!(file, "**Score:** {:.2}", m.score)?;
+    for (name, value) in issue_details(m).into_iter().chain(issue_dates(m)) {
+        writeln!(file, "**{}:** {}", name, value)?;
+    }
+    writeln!(file)?;
+
+    // Match locations
+    let mut flags = Vec::new();
+    if m.in_title {
+        flags.push("title");
+    }
+    if m.in_desc {
+        flags.push("description");
+    }
+    if !flags.is_empty() {
+        writeln!(file, "**Matched in:** {}", flags.join(", "))?;
+    }
+    if !m.matched_terms.is_empty() {
+        let terms: Vec<String> = m.matched_terms.iter().map(|t| format!("`{}`", t)).collect();
+        writeln!(file, "**Matched terms:** {}", terms.join(", "))?;
+    }
+    if !m.matched_words.is_empty() {
+        let words: Vec<String> = m.matched_words.iter().map(|w| format!("`{}`", w)).collect();
+        writeln!(file, "**Matched words:** {}", words.join(", "))?;
+    }
+    for snippet in &m.desc_snippets {
+        writeln!(file)?;
+        writeln!(file, "> {}", highlight_markdown(snippet))?;
+    }
 
-        // Comment matches
-        if !m.comments_matched.is_empty() {
-            writeln!(file)?;
+    // Comment matches
+    if !m.comments_matched.is_empty() {
+        writeln!(file)?;
+        writeln!(
+            file,
+            "**💬 {} comment(s) matched:**",
+            m.comments_matched.len()
+        )?;
+        writeln!(file)?;
+        for c in &m.comments_matched {
             writeln!(
                 file,
-                "**💬 {} comment(s) matched:**",
-                m.comments_matched.len()
+                "- **{}** ({})",
+                c.commenter,
+                c.created_at.format("%Y-%m-%d %H:%M")
             )?;
-            writeln!(file)?;
-            for c in &m.comments_matched {
-                writeln!(file, "- **{}** ({})", c.commenter, c.created_at)?;
-                writeln!(file, "  - {}", c.url)?;
+            writeln!(file, "  - {}", c.url)?;
+            for snippet in &c.snippets {
+                writeln!(file, "  - {}", highlight_markdown(snippet))?;
             }
         }
-
-        writeln!(file)?;
-        writeln!(file, "---\n")?;
     }
 
+    writeln!(file)?;
+    writeln!(file, "---\n")?;
+
     Ok(())
 }
 